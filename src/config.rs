@@ -0,0 +1,320 @@
+//! User config file with custom color themes and default options.
+//! Lives in the config directory ($XDG_CONFIG_HOME or e.g. ~/.config on Linux)
+//! as rnapkin/config.toml or rnapkin/config.json, or wherever --config points to.
+//! Files ending with .json are read as JSON, anything else as TOML:
+//! ```toml
+//! [defaults]
+//! theme = "solarized"
+//! height = 1200
+//! angle = 90
+//! folder = "RNAfold --noPS"
+//!
+//! [themes.solarized]
+//! base = "bright"          # built in theme providing whatever is left out
+//! bg = "#fdf6e3"
+//! fg = "#657b83"
+//! a = "#b58900"
+//! highlights = ["#dc322f", "#268bd2"]
+//! ```
+use std::collections::HashMap;
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use plotters::style::{RGBAColor, RGBColor};
+use serde::Deserialize;
+
+use crate::draw::colors::{parse_hex, ColorTheme};
+
+/// Config file that can't be read, doesn't parse or defines nonsense
+#[derive(Debug)]
+pub enum ConfigError {
+    Io { path: String, source: io::Error },
+    Invalid { path: String, reason: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "could not read config {path}: {source}"),
+            Self::Invalid { path, reason } => write!(f, "config {path}: {reason}"),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub defaults: Defaults,
+    #[serde(default)]
+    pub themes: HashMap<String, ThemeSpec>,
+    /// where it was read from; for error messages
+    #[serde(skip)]
+    path: String,
+}
+
+/// used whenever the option is not given on the command line
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Defaults {
+    pub theme: Option<String>,
+    pub height: Option<u32>,
+    pub angle: Option<f64>,
+    pub bgopacity: Option<f64>,
+    /// font family of --title
+    pub title_font: Option<String>,
+    /// external folding program e.g. "RNAfold --noPS"; same as --folder
+    pub folder: Option<String>,
+}
+
+/// every color is a hex string; missing ones are taken from the base theme
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThemeSpec {
+    /// one of the built in themes; dark by default
+    pub base: Option<String>,
+    pub a: Option<String>,
+    pub c: Option<String>,
+    pub g: Option<String>,
+    pub u: Option<String>,
+    pub x: Option<String>,
+    pub bg: Option<String>,
+    pub fg: Option<String>,
+    pub knot: Option<String>,
+    pub backbone: Option<String>,
+    pub bond: Option<String>,
+    pub letter: Option<String>,
+    pub missing: Option<String>,
+    /// up to 9 colors for highlight groups 1-9
+    pub highlights: Option<Vec<String>>,
+}
+
+impl ThemeSpec {
+    fn build(&self) -> Result<ColorTheme, String> {
+        let base = self.base.as_deref().unwrap_or("dark");
+        let mut theme =
+            ColorTheme::by_name(base).ok_or(format!("unknown base theme \"{base}\""))?;
+
+        let color = |field: &str, hex: &Option<String>, color: &mut RGBColor| {
+            if let Some(hex) = hex {
+                *color = parse_hex(hex).ok_or(format!("{field}: \"{hex}\" is not a hex color"))?;
+            }
+            Ok::<_, String>(())
+        };
+        color("a", &self.a, &mut theme.a)?;
+        color("c", &self.c, &mut theme.c)?;
+        color("g", &self.g, &mut theme.g)?;
+        color("u", &self.u, &mut theme.u)?;
+        color("x", &self.x, &mut theme.x)?;
+        color("fg", &self.fg, &mut theme.fg)?;
+        color("knot", &self.knot, &mut theme.knot)?;
+        color("backbone", &self.backbone, &mut theme.backbone)?;
+        color("bond", &self.bond, &mut theme.bond)?;
+        color("letter", &self.letter, &mut theme.letter)?;
+        color("missing", &self.missing, &mut theme.missing)?;
+
+        let mut bg = RGBColor(theme.bg.0, theme.bg.1, theme.bg.2);
+        color("bg", &self.bg, &mut bg)?;
+        theme.bg = RGBAColor(bg.0, bg.1, bg.2, theme.bg.3);
+
+        if let Some(highlights) = &self.highlights {
+            if highlights.len() > theme.highlights.len() {
+                return Err(format!(
+                    "highlights: at most {} colors, got {}",
+                    theme.highlights.len(),
+                    highlights.len()
+                ));
+            }
+            for (hex, slot) in highlights.iter().zip(theme.highlights.iter_mut()) {
+                color("highlights", &Some(hex.clone()), slot)?;
+            }
+        }
+        Ok(theme)
+    }
+}
+
+/// names looked for in the rnapkin config directory, in this order
+const FILE_NAMES: [&str; 2] = ["config.toml", "config.json"];
+
+/// $XDG_CONFIG_HOME if set to an absolute path, the platform config dir otherwise
+fn config_dir(xdg: Option<OsString>) -> Option<PathBuf> {
+    match xdg.map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => Some(dir),
+        _ => dirs::config_dir(),
+    }
+}
+
+impl Config {
+    /// the rnapkin directory of the user config dir
+    fn default_dir() -> Option<PathBuf> {
+        config_dir(std::env::var_os("XDG_CONFIG_HOME")).map(|dir| dir.join("rnapkin"))
+    }
+
+    /// config.toml or config.json, whichever exists, in [Self::default_dir]
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = Self::default_dir()?;
+        FILE_NAMES
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+    }
+
+    /// JSON if the path ends with .json, TOML otherwise
+    pub fn parse(text: &str, path: &str) -> Result<Self, ConfigError> {
+        let json = Path::new(path).extension() == Some(OsStr::new("json"));
+        let parsed = match json {
+            true => serde_json::from_str(text)
+                .map_err(|err| format!("{} at line {}", strip_position(&err), err.line())),
+            false => toml::from_str(text).map_err(|err| match err.span() {
+                Some(span) => {
+                    let line = text[..span.start].matches('\n').count() + 1;
+                    format!("{} at line {line}", err.message())
+                }
+                None => err.message().to_owned(),
+            }),
+        };
+        let mut config: Self = parsed.map_err(|reason| ConfigError::Invalid {
+            path: path.to_owned(),
+            reason,
+        })?;
+        config.path = path.to_owned();
+        Ok(config)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref().display().to_string();
+        let text = std::fs::read_to_string(&path).map_err(|source| ConfigError::Io {
+            path: path.clone(),
+            source,
+        })?;
+        Self::parse(&text, &path)
+    }
+
+    /// file given explicitly has to exist, the default one may be missing
+    pub fn load(path: Option<&str>) -> Result<Self, ConfigError> {
+        match path {
+            Some(path) => Self::from_file(path),
+            None => match Self::default_path() {
+                Some(path) => Self::from_file(path),
+                None => Ok(Self::default()),
+            },
+        }
+    }
+
+    /// theme defined in the config or a built in one of that name;
+    /// config themes shadow built in ones
+    pub fn theme(&self, name: &str) -> Result<Option<ColorTheme>, ConfigError> {
+        match self.themes.get(name) {
+            Some(spec) => spec
+                .build()
+                .map(Some)
+                .map_err(|reason| ConfigError::Invalid {
+                    path: self.path.clone(),
+                    reason: format!("theme \"{name}\": {reason}"),
+                }),
+            None => Ok(ColorTheme::by_name(name)),
+        }
+    }
+}
+
+/// serde_json appends "at line 3 column 7"; only the line is kept like for TOML
+fn strip_position(err: &serde_json::Error) -> String {
+    let message = err.to_string();
+    match message.rfind(" at line ") {
+        Some(at) => message[..at].to_owned(),
+        None => message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r##"
+[defaults]
+theme = "paper"
+height = 1200
+folder = "RNAfold --noPS"
+
+[themes.paper]
+base = "white"
+a = "#000000"
+letter = "#ffffff"
+highlights = ["#ff0000", "#00ff00"]
+
+[themes.broken]
+g = "#12345"
+"##;
+
+    #[test]
+    fn custom_themes() {
+        let config = Config::parse(CONFIG, "config.toml").unwrap();
+        assert_eq!(config.defaults.height, Some(1200));
+        assert_eq!(config.defaults.angle, None);
+        assert_eq!(config.defaults.folder.as_deref(), Some("RNAfold --noPS"));
+
+        let paper = config.theme("paper").unwrap().unwrap();
+        let white = ColorTheme::white();
+        assert_eq!(paper.a, RGBColor(0, 0, 0));
+        assert_eq!(paper.letter, RGBColor(255, 255, 255));
+        assert_eq!(paper.highlights[1], RGBColor(0, 255, 0));
+        assert_eq!(paper.highlights[2], white.highlights[2]);
+        assert_eq!((paper.bg.0, paper.bg.1, paper.bg.2), (255, 255, 255));
+        assert_eq!(paper.c, white.c);
+
+        assert!(config.theme("dark").unwrap().is_some());
+        assert!(config.theme("nope").unwrap().is_none());
+        let err = config.theme("broken").unwrap_err().to_string();
+        assert!(err.contains("theme \"broken\"") && err.contains("#12345"));
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let err = Config::parse("[defaults]\nhieght = 3", "config.toml").unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("config config.toml: unknown field `hieght`"));
+        assert!(err.to_string().ends_with("at line 2"));
+        assert!(Config::parse("", "config.toml").unwrap().themes.is_empty());
+    }
+
+    #[test]
+    fn json_config() {
+        let json = r##"{
+            "defaults": { "theme": "paper", "angle": 90 },
+            "themes": { "paper": { "base": "white", "a": "#000000" } }
+        }"##;
+        let config = Config::parse(json, "config.json").unwrap();
+        assert_eq!(config.defaults.angle, Some(90.));
+        let paper = config.theme("paper").unwrap().unwrap();
+        assert_eq!(paper.a, RGBColor(0, 0, 0));
+
+        let err = Config::parse("{\n\"defaults\": {\"hieght\": 3}}", "config.json").unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("config config.json: unknown field `hieght`"));
+        assert!(err.to_string().ends_with("at line 2"));
+    }
+
+    #[test]
+    fn xdg_config_home() {
+        let xdg = Some(OsString::from("/tmp/xdg"));
+        assert_eq!(config_dir(xdg), Some(PathBuf::from("/tmp/xdg")));
+        // relative paths are invalid per the spec and ignored
+        let relative = Some(OsString::from("xdg"));
+        assert_eq!(config_dir(relative), dirs::config_dir());
+        assert_eq!(config_dir(None), dirs::config_dir());
+    }
+}
@@ -1,8 +1,29 @@
+mod arc;
+mod callout;
+mod circle;
+mod colormap;
 pub mod colors;
+mod declutter;
 mod gather;
+pub mod layout;
+mod legend;
+mod mountain;
+mod numbering;
+mod output;
 mod plot;
 mod point;
+mod title;
 
-pub use gather::gather_bubbles;
-pub use plot::{plot, Mirror};
+pub use arc::plot_arcs;
+pub use callout::Region;
+pub use circle::plot_circle;
+pub use colormap::{Colormap, DataTrack};
+pub use declutter::{count_overlaps, declutter};
+pub use gather::{gather_bubbles, Bubble, BubbleVec};
+pub use layout::{Layout, LayoutKind};
+pub use legend::{Corner, Legend};
+pub use mountain::{mountain, plot_mountain, Track};
+pub use numbering::Numbering;
+pub use plot::{bonds, plot, Extras, LineStyle, Mirror};
 pub use point::Point;
+pub use title::{Position, Title};
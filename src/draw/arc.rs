@@ -0,0 +1,244 @@
+//! Arc diagram: nucleotides in a row, every pair a semicircle above them.
+//! Another structure of the same molecule may be drawn below the line;
+//! pairs that only one of the two structures has are then drawn
+//! in the first highlight color so the differences stand out.
+use std::f64::consts::PI;
+use std::path::Path;
+
+use anyhow::Result;
+use plotters::coord::types::RangedCoordf64;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+
+use super::colors::ColorTheme;
+use super::legend::{legend_column, with_legend};
+use super::output::{bottom_strip, draw_bottom, render, stroke, Painter};
+use super::plot::{Extras, LineStyle};
+use super::title::{title_strip, with_title};
+use crate::rnamanip::Nucleotide;
+
+/// canvas never gets wider than that no matter how flat the arcs are
+const MAX_WIDTH: f64 = 16384.;
+/// room left of the first and right of the last nt for 5' 3' labels
+const SIDE: f64 = 2.;
+
+struct Arcs<'a> {
+    seq: &'a [Nucleotide],
+    pair_list: &'a [Option<usize>],
+    compare: Option<&'a [Option<usize>]>,
+    theme: &'a ColorTheme,
+    extras: &'a Extras,
+    /// pixels per nt
+    px: f64,
+    /// (top, bottom) of the plot in nt units
+    ys: (f64, f64),
+    strip: u32,
+    column: u32,
+    title: u32,
+}
+
+/// widest pair span / 2 i.e. the height of the tallest arc
+fn tallest(pairs: impl Iterator<Item = (usize, usize)>) -> f64 {
+    pairs
+        .map(|(i, j)| j.abs_diff(i) as f64 / 2.)
+        .fold(0., f64::max)
+}
+
+fn pairs(pair_list: &[Option<usize>]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    pair_list
+        .iter()
+        .enumerate()
+        .filter_map(|(i, p)| p.filter(|&j| j > i).map(|j| (i, j)))
+}
+
+/// semicircle from i to j; upwards if side is 1, downwards if -1
+fn semicircle(i: usize, j: usize, side: f64) -> Vec<(f64, f64)> {
+    let center = (i + j) as f64 / 2.;
+    let radius = (j - i) as f64 / 2.;
+    let samples = (radius as usize * 8).clamp(48, 512);
+    (0..=samples)
+        .map(|k| {
+            let angle = PI * k as f64 / samples as f64;
+            (
+                center - radius * angle.cos(),
+                side * (0.5 + radius * angle.sin()),
+            )
+        })
+        .collect()
+}
+
+impl Arcs<'_> {
+    fn width(&self) -> u32 {
+        (self.px * (self.seq.len() as f64 - 1. + 2. * SIDE)).round() as u32
+    }
+
+    fn height(&self) -> u32 {
+        (self.px * (self.ys.0 - self.ys.1)).round() as u32
+    }
+
+    fn draw<D: DrawingBackend>(
+        &self,
+        root: &DrawingArea<D, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+    ) -> Result<()> {
+        let theme = self.theme;
+        let radius = 0.45 * self.px;
+        let width = (radius * 0.3).max(1.);
+        let differs = |pair_list: &[Option<usize>], other: Option<&[Option<usize>]>, i, j| {
+            other.is_some_and(|other| other[i] != Some(j)) && pair_list[i] == Some(j)
+        };
+
+        let mut top: Vec<(usize, usize)> = pairs(self.pair_list).collect();
+        top.extend(self.extras.knots.iter().copied());
+        let mut merged = self.pair_list.to_vec();
+        for &(i, j) in &self.extras.knots {
+            merged[i] = Some(j);
+            merged[j] = Some(i);
+        }
+
+        for (i, j) in top {
+            let knot = self.extras.knots.contains(&(i, j));
+            let (color, line) = match (differs(&merged, self.compare, i, j), knot) {
+                (true, _) => (&theme.highlights[0], LineStyle::Solid),
+                (false, true) => (&theme.knot, self.extras.knot_style),
+                (false, false) => (&theme.fg, LineStyle::Solid),
+            };
+            stroke(root, semicircle(i, j, 1.), color, width, line);
+        }
+
+        if let Some(compare) = self.compare {
+            for (i, j) in pairs(compare) {
+                let color = match differs(compare, Some(&merged), i, j) {
+                    true => &theme.highlights[0],
+                    false => &theme.fg,
+                };
+                stroke(root, semicircle(i, j, -1.), color, width, LineStyle::Solid);
+            }
+        }
+
+        let center = Pos::new(HPos::Center, VPos::Center);
+        let letters = TextStyle::from(("mono", 0.8 * radius).into_font())
+            .pos(center)
+            .color(&theme.letter);
+        for (pos, nt) in self.seq.iter().enumerate() {
+            let (letter, color) = nt.extract_text_and_color(theme);
+            let color = self.extras.bubble_color(pos, color, theme);
+            let at = (pos as f64, 0.);
+            root.draw(&(EmptyElement::at(at) + Circle::new((0, 0), radius, color.filled())))
+                .unwrap();
+            if let Some(&Some(hl)) = self.extras.highlights.get(pos) {
+                let ring = hl.stroke_width(width as u32);
+                root.draw(&(EmptyElement::at(at) + Circle::new((0, 0), radius, ring)))
+                    .unwrap();
+            }
+            // letters would be unreadable anyway
+            if radius >= 5. {
+                root.draw(&Text::new(letter, at, letters.clone())).unwrap();
+            }
+        }
+
+        let ends = TextStyle::from(("mono", 1.1 * radius.max(8.)).into_font())
+            .pos(center)
+            .color(&theme.fg);
+        let last = self.seq.len() as f64 - 1.;
+        root.draw(&Text::new("5'", (-SIDE / 2. - 0.2, 0.), ends.clone()))
+            .unwrap();
+        root.draw(&Text::new("3'", (last + SIDE / 2. + 0.2, 0.), ends))
+            .unwrap();
+        Ok(())
+    }
+}
+
+impl Painter for Arcs<'_> {
+    fn size(&self) -> (u32, u32) {
+        (
+            self.width() + self.column,
+            self.height() + self.strip + self.title,
+        )
+    }
+
+    fn paint<D: DrawingBackend>(&self, root: DrawingArea<D, Shift>) -> Result<()> {
+        root.fill(&self.theme.bg).unwrap();
+        let font = 0.45 * self.px.max(20.);
+        let root = with_title(root, self.extras, self.title, self.height(), self.theme)?;
+        let root = with_legend(root, self.extras, self.column, font, self.theme)?;
+        let (upper, lower) = root.split_vertically(self.height());
+        let last = self.seq.len() as f64 - 1.;
+        let upper = upper.apply_coord_spec(Cartesian2d::<RangedCoordf64, RangedCoordf64>::new(
+            -SIDE..last + SIDE,
+            self.ys.0..self.ys.1,
+            upper.get_pixel_range(),
+        ));
+        self.draw(&upper)?;
+        draw_bottom(&lower, font, self.theme, self.extras)?;
+        Ok(())
+    }
+}
+
+/// draws the arc diagram; pair_list holds nested pairs while crossing
+/// ones come through extras.knots. compare, if given, is a full pair list
+/// drawn below the line. Height is the upper bound; very flat diagrams
+/// are scaled down so they don't get absurdly wide
+pub fn plot_arcs<P: AsRef<Path>>(
+    seq: &[Nucleotide],
+    pair_list: &[Option<usize>],
+    compare: Option<&[Option<usize>]>,
+    filename: &P,
+    theme: &ColorTheme,
+    height: u32,
+    extras: &Extras,
+) -> Result<Option<String>> {
+    let above = tallest(pairs(pair_list).chain(extras.knots.iter().copied()));
+    let below = compare.map_or(0., |c| tallest(pairs(c)));
+    let ys = (above + 1.5, -(below + 1.5));
+
+    let span = ys.0 - ys.1;
+    let wide = seq.len() as f64 - 1. + 2. * SIDE;
+    let px = (height as f64 / span).min(MAX_WIDTH / wide);
+    let font = 0.45 * px.max(20.);
+    let mut arcs = Arcs {
+        seq,
+        pair_list,
+        compare,
+        theme,
+        extras,
+        px,
+        ys,
+        strip: bottom_strip(extras, font),
+        column: 0,
+        title: 0,
+    };
+    arcs.column = legend_column(&extras.legend, font, arcs.height() + arcs.strip);
+    arcs.title = title_strip(extras, arcs.height());
+    render(&arcs, filename)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rnamanip::get_pair_list;
+
+    #[test]
+    fn arcs_and_differences() {
+        let seq = vec![Nucleotide::A; 12];
+        let top = get_pair_list("((((....))))").unwrap();
+        let bottom = get_pair_list(".(((....))).").unwrap();
+        let theme = ColorTheme::dark();
+        let svg = plot_arcs(
+            &seq,
+            &top,
+            Some(&bottom),
+            &"o.x",
+            &theme,
+            400,
+            &Extras::default(),
+        )
+        .unwrap()
+        .expect("svg string");
+        assert_eq!(svg.matches("<polyline").count(), 7);
+        // only the outermost pair is missing below
+        let RGBColor(r, g, b) = theme.highlights[0];
+        let differing = format!("stroke=\"#{r:02X}{g:02X}{b:02X}\"");
+        assert_eq!(svg.matches(&differing).count(), 1);
+    }
+}
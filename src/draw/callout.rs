@@ -0,0 +1,199 @@
+//! Named regions labeled right on the drawing. Every label is pushed out of
+//! its region until it covers neither bubbles nor other labels, preferably
+//! away from the middle of the molecule, and is tied back to the region
+//! by a leader line.
+use plotters::style::RGBColor;
+
+use super::gather::BubbleVec;
+use super::output::text_width;
+use super::Point;
+
+/// text height relative to bubble radius
+pub(crate) const TEXT: f64 = 1.2;
+/// directions tried around the region
+const DIRECTIONS: usize = 24;
+/// how far, in bubble radii, a label may be pushed
+const REACH: usize = 16;
+
+/// part of the molecule to name on the drawing
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub positions: Vec<usize>,
+    pub text: String,
+    /// leader line color
+    pub color: RGBColor,
+}
+
+/// placed label; all in layout coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Callout {
+    pub text: String,
+    pub color: RGBColor,
+    /// center of the text
+    pub at: Point,
+    /// half width and half height of the text box
+    pub half: Point,
+    /// leader line from the region to the text
+    pub leader: (Point, Point),
+}
+
+impl Callout {
+    fn overlaps(&self, at: Point, half: Point) -> bool {
+        (self.at.x - at.x).abs() < self.half.x + half.x
+            && (self.at.y - at.y).abs() < self.half.y + half.y
+    }
+
+    /// corners of the text box
+    pub fn corners(&self) -> [Point; 2] {
+        [self.at - self.half, self.at + self.half]
+    }
+}
+
+fn centroid<'a>(points: impl Iterator<Item = &'a Point>) -> Option<Point> {
+    let (sum, n) = points.fold((Point::default(), 0.), |(sum, n), p| (sum + *p, n + 1.));
+    (n > 0.).then(|| Point::new(sum.x / n, sum.y / n))
+}
+
+/// distance between point and the box around at
+fn to_box(p: Point, at: Point, half: Point) -> f64 {
+    let dx = ((p.x - at.x).abs() - half.x).max(0.);
+    let dy = ((p.y - at.y).abs() - half.y).max(0.);
+    Point::new(dx, dy).length()
+}
+
+/// where the segment from box center towards p leaves the box
+fn box_exit(at: Point, half: Point, p: Point) -> Point {
+    let d = p - at;
+    let t = [half.x / d.x.abs(), half.y / d.y.abs(), 1.]
+        .into_iter()
+        .fold(f64::INFINITY, f64::min);
+    Point::new(at.x + d.x * t, at.y + d.y * t)
+}
+
+/// places labels one region at a time; obstacles are other things
+/// already on the drawing (e.g. position numbers) as box centers
+pub(crate) fn place(
+    bblv: &BubbleVec,
+    bblr: f64,
+    regions: &[Region],
+    obstacles: &[Point],
+) -> Vec<Callout> {
+    let lookup = bblv.pos_lookup();
+    let middle = centroid(bblv.bubbles.iter().map(|b| &b.point)).unwrap_or_default();
+    let mut callouts: Vec<Callout> = vec![];
+
+    for region in regions {
+        let points: Vec<Point> = region
+            .positions
+            .iter()
+            .filter_map(|&pos| lookup.get(pos).map(|&idx| bblv[idx].point))
+            .collect();
+        let Some(center) = centroid(points.iter()) else {
+            continue;
+        };
+
+        let height = TEXT * bblr;
+        let width = text_width(&region.text, height);
+        let half = Point::new(width / 2. + 0.2 * bblr, height / 2. + 0.2 * bblr);
+
+        let outward = match center.distance(middle) > 1e-9 {
+            true => (center.y - middle.y).atan2(center.x - middle.x),
+            false => std::f64::consts::FRAC_PI_2,
+        };
+        // directions closest to outward first
+        let angles: Vec<f64> = (0..DIRECTIONS)
+            .map(|k| {
+                let step = std::f64::consts::TAU / DIRECTIONS as f64;
+                let turn = (k as f64 / 2.).ceil() * step;
+                outward + if k % 2 == 0 { turn } else { -turn }
+            })
+            .collect();
+
+        let free = |at: Point| {
+            bblv.bubbles
+                .iter()
+                .all(|b| to_box(b.point, at, half) > 1.1 * bblr)
+                && obstacles.iter().all(|&o| to_box(o, at, half) > bblr)
+                && callouts.iter().all(|c| !c.overlaps(at, half))
+        };
+        let candidate = |step: usize, angle: f64| {
+            let reach = (1. + step as f64) * 2. * bblr;
+            Point::new(
+                center.x + reach * angle.cos(),
+                center.y + reach * angle.sin(),
+            )
+        };
+
+        let at = (0..REACH)
+            .flat_map(|step| angles.iter().map(move |&angle| (step, angle)))
+            .map(|(step, angle)| candidate(step, angle))
+            .find(|&at| free(at))
+            .unwrap_or_else(|| candidate(REACH, outward));
+
+        let anchor = points
+            .iter()
+            .copied()
+            .min_by(|a, b| a.distance(at).total_cmp(&b.distance(at)))
+            .expect("region has at least one point");
+        let reach = anchor.distance(at).max(1e-9);
+        let start = anchor
+            + Point::new(
+                (at.x - anchor.x) / reach * 1.1 * bblr,
+                (at.y - anchor.y) / reach * 1.1 * bblr,
+            );
+
+        callouts.push(Callout {
+            text: region.text.clone(),
+            color: region.color,
+            at,
+            half,
+            leader: (start, box_exit(at, half, anchor)),
+        });
+    }
+    callouts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::draw::gather_bubbles;
+    use crate::forest;
+    use crate::rnamanip::{get_pair_list, Nucleotide};
+
+    #[test]
+    fn callouts_avoid_bubbles_and_each_other() {
+        let pair_list = get_pair_list("((((((....))))))....((((((....))))))").unwrap();
+        let seq = vec![Nucleotide::X; pair_list.len()];
+        let bblv = gather_bubbles(&forest::grow_tree(&pair_list), &seq, 0.5, 0.);
+        let region = |positions: Vec<usize>, text: &str| Region {
+            positions,
+            text: text.to_owned(),
+            color: RGBColor(0, 0, 0),
+        };
+        let regions = [
+            region((0..6).chain(10..16).collect(), "P1"),
+            region((6..10).collect(), "loop"),
+            region((6..10).collect(), "also the loop"),
+        ];
+
+        let callouts = place(&bblv, 0.5, &regions, &[]);
+        assert_eq!(callouts.len(), 3);
+        for (i, callout) in callouts.iter().enumerate() {
+            for bbl in &bblv.bubbles {
+                assert!(to_box(bbl.point, callout.at, callout.half) > 0.5);
+            }
+            for other in &callouts[i + 1..] {
+                assert!(!other.overlaps(callout.at, callout.half));
+            }
+        }
+    }
+
+    #[test]
+    fn leader_ends_on_the_box() {
+        let half = Point::new(2., 1.);
+        let exit = box_exit(Point::new(0., 0.), half, Point::new(10., 0.));
+        assert_eq!(exit, Point::new(2., 0.));
+        let exit = box_exit(Point::new(0., 0.), half, Point::new(0., -5.));
+        assert_eq!(exit, Point::new(0., -1.));
+    }
+}
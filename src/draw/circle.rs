@@ -0,0 +1,206 @@
+//! Circle plot: nucleotides go clockwise around a circle starting at the top
+//! and every base pair is a chord bowing towards the middle.
+//! Unlike the napkin it needs no layout at all so pseudoknots
+//! and long range interactions are shown just as they are.
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+use std::path::Path;
+
+use anyhow::Result;
+use plotters::coord::types::RangedCoordf64;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+
+use super::colors::ColorTheme;
+use super::legend::{legend_column, with_legend};
+use super::output::{bezier, bottom_strip, draw_bottom, render, stroke, Painter};
+use super::plot::{Extras, LineStyle};
+use super::title::{title_strip, with_title};
+use super::Point;
+use crate::rnamanip::Nucleotide;
+
+/// how far past the unit circle the canvas reaches
+const REACH: f64 = 1.2;
+
+struct Circle<'a> {
+    seq: &'a [Nucleotide],
+    pair_list: &'a [Option<usize>],
+    theme: &'a ColorTheme,
+    extras: &'a Extras,
+    height: u32,
+    strip: u32,
+    column: u32,
+    title: u32,
+}
+
+impl Circle<'_> {
+    /// angle between two neighbouring nts; leaves a gap of 2 slots at the top
+    fn slice(&self) -> f64 {
+        TAU / (self.seq.len() + 2) as f64
+    }
+
+    fn point(&self, pos: usize, radius: f64) -> Point {
+        let angle = FRAC_PI_2 - self.slice() * (pos + 1) as f64 - self.slice() / 2.;
+        Point::new(radius * angle.cos(), radius * angle.sin())
+    }
+
+    /// bubble radius in circle units; neighbours touch unless there are few of them
+    fn bblr(&self) -> f64 {
+        (self.slice() / 2. * 0.95).min(0.06)
+    }
+
+    /// chord from pos to pair bowing towards the center the more
+    /// the closer the two are along the circle
+    fn chord(&self, pos: usize, pair: usize) -> Vec<(f64, f64)> {
+        let rim = 1. - self.bblr();
+        let (p0, p1) = (self.point(pos, rim), self.point(pair, rim));
+        let apart = self.slice() * pos.abs_diff(pair) as f64;
+        let sep = apart.min(TAU - apart) / PI;
+        let mid = p0.get_middle(p1);
+        let ctrl = Point::new(mid.x * (1. - sep), mid.y * (1. - sep));
+        bezier(p0, ctrl, p1, 64)
+    }
+
+    fn draw<D: DrawingBackend>(
+        &self,
+        root: &DrawingArea<D, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+        px: f64,
+    ) -> Result<()> {
+        let theme = self.theme;
+        let radius = self.bblr() * px;
+        let width = (radius * 0.3).max(1.);
+
+        for (pos, pair) in self.pair_list.iter().enumerate() {
+            match pair {
+                Some(pair) if *pair > pos => stroke(
+                    root,
+                    self.chord(pos, *pair),
+                    &theme.fg,
+                    width,
+                    LineStyle::Solid,
+                ),
+                _ => (),
+            }
+        }
+        for &(pos, pair) in &self.extras.knots {
+            stroke(
+                root,
+                self.chord(pos, pair),
+                &theme.knot,
+                width,
+                self.extras.knot_style,
+            );
+        }
+
+        let center = Pos::new(HPos::Center, VPos::Center);
+        let letters = TextStyle::from(("mono", 0.8 * radius).into_font())
+            .pos(center)
+            .color(&theme.letter);
+        for (pos, nt) in self.seq.iter().enumerate() {
+            let (letter, color) = nt.extract_text_and_color(theme);
+            let color = self.extras.bubble_color(pos, color, theme);
+            let p = self.point(pos, 1.);
+            let bubble = EmptyElement::at((p.x, p.y))
+                + plotters::element::Circle::new((0, 0), radius, color.filled());
+            root.draw(&bubble).unwrap();
+            if let Some(&Some(hl)) = self.extras.highlights.get(pos) {
+                let ring = EmptyElement::at((p.x, p.y))
+                    + plotters::element::Circle::new(
+                        (0, 0),
+                        radius,
+                        hl.stroke_width((radius * 0.3).max(1.) as u32),
+                    );
+                root.draw(&ring).unwrap();
+            }
+            // letters would be unreadable anyway
+            if radius >= 5. {
+                root.draw(&Text::new(letter, (p.x, p.y), letters.clone()))
+                    .unwrap();
+            }
+        }
+
+        let ends = TextStyle::from(("mono", (px / 25.).max(1.1 * radius)).into_font())
+            .pos(center)
+            .color(&theme.fg);
+        if let Some(last) = self.seq.len().checked_sub(1) {
+            let outside = 1. + 3. * self.bblr();
+            let (p5, p3) = (self.point(0, outside), self.point(last, outside));
+            root.draw(&Text::new("5'", (p5.x, p5.y), ends.clone()))
+                .unwrap();
+            root.draw(&Text::new("3'", (p3.x, p3.y), ends)).unwrap();
+        }
+        Ok(())
+    }
+}
+
+impl Painter for Circle<'_> {
+    fn size(&self) -> (u32, u32) {
+        (
+            self.height + self.column,
+            self.height + self.strip + self.title,
+        )
+    }
+
+    fn paint<D: DrawingBackend>(&self, root: DrawingArea<D, Shift>) -> Result<()> {
+        root.fill(&self.theme.bg).unwrap();
+        // pixels per circle unit
+        let px = self.height as f64 / (2. * REACH);
+        let root = with_title(root, self.extras, self.title, self.height, self.theme)?;
+        let root = with_legend(root, self.extras, self.column, px / 20., self.theme)?;
+        let (upper, lower) = root.split_vertically(self.height);
+        let upper = upper.apply_coord_spec(Cartesian2d::<RangedCoordf64, RangedCoordf64>::new(
+            -REACH..REACH,
+            REACH..-REACH,
+            upper.get_pixel_range(),
+        ));
+        self.draw(&upper, px)?;
+        draw_bottom(&lower, px / 20., self.theme, self.extras)?;
+        Ok(())
+    }
+}
+
+/// draws the circle plot; pair_list holds nested pairs
+/// while crossing ones come through extras.knots like for the napkin
+pub fn plot_circle<P: AsRef<Path>>(
+    seq: &[Nucleotide],
+    pair_list: &[Option<usize>],
+    filename: &P,
+    theme: &ColorTheme,
+    height: u32,
+    extras: &Extras,
+) -> Result<Option<String>> {
+    let px = height as f64 / (2. * REACH);
+    let strip = bottom_strip(extras, px / 20.);
+    let circle = Circle {
+        seq,
+        pair_list,
+        theme,
+        extras,
+        height,
+        strip,
+        column: legend_column(&extras.legend, px / 20., height + strip),
+        title: title_strip(extras, height),
+    };
+    render(&circle, filename)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn circle_plot_svg() {
+        let seq = vec![Nucleotide::G; 12];
+        let pair_list = crate::rnamanip::get_pair_list("((((....))))").unwrap();
+        let extras = Extras {
+            knots: vec![(4, 7)],
+            ..Extras::default()
+        };
+        let svg = plot_circle(&seq, &pair_list, &"o.x", &ColorTheme::dark(), 300, &extras)
+            .unwrap()
+            .expect("svg string");
+        // 4 chords + knot, 12 bubbles
+        assert_eq!(svg.matches("<polyline").count(), 5);
+        assert_eq!(svg.matches("<circle").count(), 12);
+    }
+}
@@ -0,0 +1,261 @@
+//! Continuous coloring of bubbles by per-nucleotide data
+//! e.g. SHAPE reactivity, conservation or pairing probability,
+//! along with the colorbar explaining it.
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+
+use super::colors::ColorTheme;
+use super::output::text_width;
+
+const VIRIDIS: [(f64, RGBColor); 9] = [
+    (0., RGBColor(68, 1, 84)),
+    (0.125, RGBColor(71, 44, 122)),
+    (0.25, RGBColor(59, 81, 139)),
+    (0.375, RGBColor(44, 113, 142)),
+    (0.5, RGBColor(33, 144, 141)),
+    (0.625, RGBColor(39, 173, 129)),
+    (0.75, RGBColor(92, 200, 99)),
+    (0.875, RGBColor(170, 220, 50)),
+    (1., RGBColor(253, 231, 37)),
+];
+
+/// the usual SHAPE thresholds: below 0.4 low, above 0.85 high
+const REACTIVITY: [(f64, RGBColor); 4] = [
+    (0., RGBColor(230, 230, 230)),
+    (0.4, RGBColor(245, 190, 60)),
+    (0.85, RGBColor(230, 100, 30)),
+    (1., RGBColor(200, 20, 20)),
+];
+
+const DIVERGING: [(f64, RGBColor); 5] = [
+    (0., RGBColor(33, 102, 172)),
+    (0.25, RGBColor(103, 169, 207)),
+    (0.5, RGBColor(247, 247, 247)),
+    (0.75, RGBColor(239, 138, 98)),
+    (1., RGBColor(178, 24, 43)),
+];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Colormap {
+    #[default]
+    Viridis,
+    /// light for unreactive, red for highly reactive; spans 0 to 1 by default
+    Reactivity,
+    /// blue, white, red; centered on 0 if data has both signs
+    Diverging,
+}
+
+impl FromStr for Colormap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viridis" => Ok(Self::Viridis),
+            "reactivity" | "shape" => Ok(Self::Reactivity),
+            "diverging" => Ok(Self::Diverging),
+            _ => Err(format!(
+                "unknown colormap: {s}; try viridis, reactivity or diverging"
+            )),
+        }
+    }
+}
+
+impl Colormap {
+    fn stops(&self) -> &'static [(f64, RGBColor)] {
+        match self {
+            Self::Viridis => &VIRIDIS,
+            Self::Reactivity => &REACTIVITY,
+            Self::Diverging => &DIVERGING,
+        }
+    }
+
+    /// t between 0 and 1; anything outside is clamped
+    pub fn color(&self, t: f64) -> RGBColor {
+        let t = t.clamp(0., 1.);
+        let stops = self.stops();
+        let upper = stops
+            .iter()
+            .position(|(at, _)| *at >= t)
+            .unwrap_or(0)
+            .max(1);
+        let ((t0, c0), (t1, c1)) = (stops[upper - 1], stops[upper]);
+        let f = (t - t0) / (t1 - t0);
+        let mix = |a: u8, b: u8| (a as f64 + f * (b as f64 - a as f64)).round() as u8;
+        RGBColor(mix(c0.0, c1.0), mix(c0.1, c1.1), mix(c0.2, c1.2))
+    }
+
+    /// range used unless one is given explicitly
+    fn default_range(&self, values: &[Option<f64>]) -> (f64, f64) {
+        let (min, max) = values
+            .iter()
+            .flatten()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &v| {
+                (min.min(v), max.max(v))
+            });
+        match self {
+            _ if min > max => (0., 1.), // no data at all
+            Self::Reactivity => (0., 1.),
+            Self::Diverging if min < 0. && max > 0. => {
+                let reach = min.abs().max(max);
+                (-reach, reach)
+            }
+            _ => (min, max),
+        }
+    }
+}
+
+/// per position values colored through a colormap
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataTrack {
+    /// indexed by position; None where there's no data
+    pub values: Vec<Option<f64>>,
+    pub colormap: Colormap,
+    /// values mapped onto the ends of the colormap
+    pub range: (f64, f64),
+}
+
+impl DataTrack {
+    pub fn new(values: Vec<Option<f64>>, colormap: Colormap) -> Self {
+        let range = colormap.default_range(&values);
+        Self {
+            values,
+            colormap,
+            range,
+        }
+    }
+
+    /// pads the values with None up to the sequence length so missing tail
+    /// shows up as no data; more values than nucleotides is an error
+    pub fn fit(mut self, len: usize) -> Result<Self> {
+        if self.values.len() > len {
+            bail!(
+                "data has {} values but the sequence only {len} nt",
+                self.values.len()
+            );
+        }
+        self.values.resize(len, None);
+        Ok(self)
+    }
+
+    fn normalize(&self, value: f64) -> f64 {
+        let (min, max) = self.range;
+        match max > min {
+            true => (value - min) / (max - min),
+            false => 0.5,
+        }
+    }
+
+    /// theme.missing where there's no data
+    pub fn color(&self, pos: usize, theme: &ColorTheme) -> RGBColor {
+        match self.values.get(pos) {
+            Some(Some(value)) => self.colormap.color(self.normalize(*value)),
+            _ => theme.missing,
+        }
+    }
+}
+
+/// height of the strip left under the plot for the colorbar
+pub(crate) fn colorbar_strip(data: &Option<DataTrack>, font: f64) -> u32 {
+    match data {
+        Some(_) => (3. * font).round() as u32,
+        None => 0,
+    }
+}
+
+/// gradient bar with the range at its ends and a swatch for missing data,
+/// right aligned within the strip so the caption can have the left side
+pub(crate) fn draw_colorbar<D: DrawingBackend>(
+    strip: &DrawingArea<D, Shift>,
+    font: f64,
+    theme: &ColorTheme,
+    data: &DataTrack,
+) -> Result<()> {
+    let (width, height) = strip.dim_in_pixel();
+    let (width, mid) = (width as f64, height as f64 / 2.);
+    let label = |text: String, x: f64, hpos: HPos| {
+        let style = TextStyle::from(("sans-serif", font).into_font())
+            .pos(Pos::new(hpos, VPos::Center))
+            .color(&theme.fg);
+        strip
+            .draw(&Text::new(text, (x as i32, mid as i32), style))
+            .unwrap();
+    };
+
+    let (min, max) = (
+        format!("{:.2}", data.range.0),
+        format!("{:.2}", data.range.1),
+    );
+    let bar_width = (10. * font).min(width / 3.);
+    let bar_end = width - 1.5 * font - text_width(&max, font);
+    let bar_start = bar_end - bar_width;
+    let (top, bottom) = ((mid - 0.5 * font) as i32, (mid + 0.5 * font) as i32);
+
+    let steps = bar_width.round().max(1.) as i32;
+    for step in 0..steps {
+        let color = data.colormap.color(step as f64 / (steps - 1).max(1) as f64);
+        let x = bar_start as i32 + step;
+        strip
+            .draw(&Rectangle::new([(x, top), (x + 1, bottom)], color.filled()))
+            .unwrap();
+    }
+    label(max, bar_end + 0.5 * font, HPos::Left);
+    label(min, bar_start - 0.5 * font, HPos::Right);
+
+    if data.values.iter().all(Option::is_some) {
+        return Ok(());
+    }
+    let na = "no data";
+    let na_end = bar_start - 1.5 * font - text_width(&format!("{:.2}", data.range.0), font);
+    label(na.to_owned(), na_end, HPos::Right);
+    let swatch_end = na_end - text_width(na, font) - 0.5 * font;
+    let swatch = [
+        ((swatch_end - font) as i32, top),
+        (swatch_end as i32, bottom),
+    ];
+    strip
+        .draw(&Rectangle::new(swatch, theme.missing.filled()))
+        .unwrap();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colormap_ends_and_middle() {
+        assert_eq!(Colormap::Viridis.color(0.), RGBColor(68, 1, 84));
+        assert_eq!(Colormap::Viridis.color(1.), RGBColor(253, 231, 37));
+        assert_eq!(Colormap::Viridis.color(7.), RGBColor(253, 231, 37));
+        assert_eq!(Colormap::Diverging.color(0.5), RGBColor(247, 247, 247));
+        assert_eq!(Colormap::Reactivity.color(0.2), RGBColor(238, 210, 145));
+    }
+
+    #[test]
+    fn data_track_ranges() {
+        let theme = ColorTheme::dark();
+        let track = DataTrack::new(vec![Some(2.), None, Some(4.)], Colormap::Viridis);
+        assert_eq!(track.range, (2., 4.));
+        assert_eq!(track.color(0, &theme), RGBColor(68, 1, 84));
+        assert_eq!(track.color(1, &theme), theme.missing);
+        assert_eq!(track.color(9, &theme), theme.missing);
+
+        let track = DataTrack::new(vec![Some(-1.), Some(3.)], Colormap::Diverging);
+        assert_eq!(track.range, (-3., 3.));
+        let track = DataTrack::new(vec![Some(7.)], Colormap::Reactivity);
+        assert_eq!(track.range, (0., 1.));
+    }
+
+    #[test]
+    fn data_track_fits_the_sequence() {
+        let track = DataTrack::new(vec![Some(2.), Some(4.)], Colormap::Viridis);
+        let fitted = track.clone().fit(4).unwrap();
+        assert_eq!(fitted.values, vec![Some(2.), Some(4.), None, None]);
+        assert_eq!(fitted.range, track.range);
+        assert!(track.fit(1).is_err());
+    }
+}
@@ -12,6 +12,10 @@ pub mod default_pallette {
     pub const DARK_X: RGBColor = RGBColor(211, 134, 155); // purple for the unknown
     pub const DARK_BG: RGBAColor = RGBAColor(40, 40, 40, 1.0); // background
     pub const DARK_FG: RGBColor = RGBColor(235, 219, 178); // lettering
+    pub const DARK_KNOT: RGBColor = RGBColor(142, 192, 124); // aqua for pseudoknots
+    pub const DARK_BACKBONE: RGBColor = RGBColor(146, 131, 116); // gray
+    pub const DARK_BOND: RGBColor = RGBColor(235, 219, 178); // same as lettering
+    pub const DARK_MISSING: RGBColor = RGBColor(102, 92, 84); // positions without data
 
     // bright ones
     pub const BRIGHT_C: RGBColor = RGBColor(152, 151, 26);
@@ -21,6 +25,10 @@ pub mod default_pallette {
     pub const BRIGHT_X: RGBColor = RGBColor(211, 134, 155);
     pub const BRIGHT_BG: RGBAColor = RGBAColor(251, 241, 199, 1.0);
     pub const BRIGHT_FG: RGBColor = RGBColor(60, 56, 54);
+    pub const BRIGHT_KNOT: RGBColor = RGBColor(104, 157, 106);
+    pub const BRIGHT_BACKBONE: RGBColor = RGBColor(124, 111, 100);
+    pub const BRIGHT_BOND: RGBColor = RGBColor(60, 56, 54);
+    pub const BRIGHT_MISSING: RGBColor = RGBColor(189, 174, 147);
 
     /// An array of 9 colors:
     /// ```text
@@ -54,6 +62,16 @@ pub fn user_input_to_highlight_indices(hls: &str) -> Vec<Option<usize>> {
         .collect()
 }
 
+/// parses "#rrggbb" or "rrggbb" into a color
+pub fn parse_hex(hex: &str) -> Option<RGBColor> {
+    let hex = hex.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(RGBColor(channel(0)?, channel(2)?, channel(4)?))
+}
+
 #[derive(Debug, Clone)]
 pub struct ColorTheme {
     /// cytosine
@@ -72,6 +90,16 @@ pub struct ColorTheme {
     pub fg: RGBColor,
     /// since 0 means no highlight; highlight1 is 0indexed and so on
     pub highlights: [RGBColor; 9],
+    /// pseudoknot connectors
+    pub knot: RGBColor,
+    /// lines between consecutive nts
+    pub backbone: RGBColor,
+    /// base pair symbols
+    pub bond: RGBColor,
+    /// nucleotide letters inside the bubbles
+    pub letter: RGBColor,
+    /// bubbles without data when coloring by data
+    pub missing: RGBColor,
 }
 
 impl ColorTheme {
@@ -85,6 +113,11 @@ impl ColorTheme {
             bg: default_pallette::DARK_BG,
             fg: default_pallette::DARK_FG,
             highlights: default_pallette::HIGHLIGHTS,
+            knot: default_pallette::DARK_KNOT,
+            backbone: default_pallette::DARK_BACKBONE,
+            bond: default_pallette::DARK_BOND,
+            letter: RGBColor(0, 0, 0),
+            missing: default_pallette::DARK_MISSING,
         }
     }
 
@@ -98,6 +131,11 @@ impl ColorTheme {
             bg: default_pallette::BRIGHT_BG,
             fg: default_pallette::BRIGHT_FG,
             highlights: default_pallette::HIGHLIGHTS,
+            knot: default_pallette::BRIGHT_KNOT,
+            backbone: default_pallette::BRIGHT_BACKBONE,
+            bond: default_pallette::BRIGHT_BOND,
+            letter: RGBColor(0, 0, 0),
+            missing: default_pallette::BRIGHT_MISSING,
         }
     }
 
@@ -116,8 +154,34 @@ impl ColorTheme {
     }
 }
 
+impl ColorTheme {
+    /// one of the built in themes; dark, bright, white/w, black/b
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "white" | "w" => Some(Self::white()),
+            "black" | "b" => Some(Self::black()),
+            "bright" => Some(Self::bright()),
+            _ => None,
+        }
+    }
+}
+
 impl Default for ColorTheme {
     fn default() -> Self {
         Self::dark()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_colors() {
+        assert_eq!(parse_hex("#850000"), Some(RGBColor(133, 0, 0)));
+        assert_eq!(parse_hex("40E0D0"), Some(RGBColor(64, 224, 208)));
+        assert_eq!(parse_hex("#85000"), None);
+        assert_eq!(parse_hex("#zz0000"), None);
+    }
+}
@@ -0,0 +1,310 @@
+//! Optional pass run after [super::gather_bubbles] that untangles branches
+//! colliding with each other, which the skeleton circles happily produce
+//! in big multiloops (rRNA domains, long lncRNA fragments).
+//!
+//! In the spirit of RNApuzzler every helix is treated as a rigid branch
+//! hinged at its first base pair. Whenever bubbles of two branches overlap,
+//! branches holding only one of them are swung around their hinge a little
+//! at a time and the angle that removes most of the overlap wins.
+//! Stems stay straight and loops keep their shape; only the directions
+//! in which the stems leave their loops change. A swing stretches the backbone
+//! bonds joining the branch to its loop, so swings stretching either of them
+//! past [MAX_STRETCH] times the bubble spacing are not taken.
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+use super::gather::BubbleVec;
+use super::point::Point;
+
+/// bubbles closer than this many diameters collide;
+/// a bit of slack since paired and neighbouring bubbles touch
+const OVERLAP: f64 = 0.8;
+/// swings tried in both directions: STEP, 2 * STEP ... MAX_SWING
+const STEP: f64 = PI / 36.;
+const MAX_SWING: f64 = PI / 3.;
+/// gives up after that many accepted swings
+const MAX_MOVES: usize = 500;
+/// longest backbone bond a swing may leave behind, in bubble diameters
+const MAX_STRETCH: f64 = 1.5;
+
+/// helix hinged at its first pair; moving it moves every position in range
+#[derive(Debug, Clone, Copy)]
+struct Branch {
+    start: usize,
+    end: usize,
+}
+
+impl Branch {
+    fn contains(&self, pos: usize) -> bool {
+        self.start <= pos && pos <= self.end
+    }
+
+    fn len(&self) -> usize {
+        self.end - self.start + 1
+    }
+}
+
+/// every pair that starts a helix i.e. isn't stacked on an enclosing pair;
+/// helices touching either end of the molecule stay put, 5' and 3' labels hang off them
+fn branches(pair_list: &[Option<usize>]) -> Vec<Branch> {
+    let last = pair_list.len().saturating_sub(1);
+    pair_list
+        .iter()
+        .enumerate()
+        .filter_map(|(i, pair)| {
+            let j = (*pair)?;
+            let stacked = i > 0 && j < last && pair_list[i - 1] == Some(j + 1);
+            (i < j && !stacked && i != 0 && j != last).then_some(Branch { start: i, end: j })
+        })
+        .collect()
+}
+
+/// buckets bubble positions by the cell they fall into
+struct Grid {
+    cell: f64,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+}
+
+impl Grid {
+    fn new(points: &[Point], cell: f64) -> Self {
+        let mut cells: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+        for (pos, p) in points.iter().enumerate() {
+            cells.entry(Self::key(*p, cell)).or_default().push(pos);
+        }
+        Self { cell, cells }
+    }
+
+    fn key(p: Point, cell: f64) -> (i64, i64) {
+        ((p.x / cell).floor() as i64, (p.y / cell).floor() as i64)
+    }
+
+    /// positions in the cells surrounding p
+    fn around(&self, p: Point) -> impl Iterator<Item = usize> + '_ {
+        let (cx, cy) = Self::key(p, self.cell);
+        (-1..=1)
+            .flat_map(move |dx| (-1..=1).map(move |dy| (cx + dx, cy + dy)))
+            .filter_map(|key| self.cells.get(&key))
+            .flatten()
+            .copied()
+    }
+}
+
+struct Declutter {
+    /// bubble centers indexed by position
+    points: Vec<Point>,
+    limit: f64,
+    /// longest allowed distance between sequence neighbours
+    max_bond: f64,
+    branches: Vec<Branch>,
+}
+
+impl Declutter {
+    fn penetration(&self, a: Point, b: Point) -> f64 {
+        (self.limit - a.distance(b)).max(0.)
+    }
+
+    /// how badly bubbles of the branch, placed at moved points,
+    /// overlap with bubbles outside of it
+    fn cost(&self, grid: &Grid, branch: Branch, moved: &[Point]) -> f64 {
+        moved
+            .iter()
+            .map(|&p| {
+                grid.around(p)
+                    .filter(|&other| !branch.contains(other))
+                    .map(|other| self.penetration(p, self.points[other]))
+                    .sum::<f64>()
+            })
+            .sum()
+    }
+
+    fn swing(&self, branch: Branch, angle: f64) -> Vec<Point> {
+        let hinge = self.points[branch.start].get_middle(self.points[branch.end]);
+        self.points[branch.start..=branch.end]
+            .iter()
+            .map(|p| p.rotate_around_origin(hinge, angle))
+            .collect()
+    }
+
+    /// bonds from the loop to the first and last nt of the moved branch stay short enough;
+    /// branches never start or end the molecule so both neighbours exist
+    fn keeps_backbone(&self, branch: Branch, moved: &[Point]) -> bool {
+        self.points[branch.start - 1].distance(moved[0]) <= self.max_bond
+            && self.points[branch.end + 1].distance(moved[moved.len() - 1]) <= self.max_bond
+    }
+
+    /// every pair of colliding positions apart from sequence neighbours
+    fn collisions(&self, grid: &Grid) -> Vec<(usize, usize)> {
+        let mut found = vec![];
+        for (pos, &p) in self.points.iter().enumerate() {
+            for other in grid.around(p) {
+                if other > pos + 1 && self.penetration(p, self.points[other]) > 0. {
+                    found.push((pos, other));
+                }
+            }
+        }
+        found
+    }
+
+    /// best swing of any branch separating a from b; (branch idx, angle, improvement)
+    fn best_swing(&self, grid: &Grid, a: usize, b: usize) -> Option<(usize, f64, f64)> {
+        let mut best: Option<(usize, f64, f64)> = None;
+        let steps = (MAX_SWING / STEP).round() as usize;
+
+        for (idx, branch) in self.branches.iter().enumerate() {
+            if branch.contains(a) == branch.contains(b) {
+                continue;
+            }
+            let current = &self.points[branch.start..=branch.end];
+            let before = self.cost(grid, *branch, current);
+
+            for k in 1..=steps {
+                for sign in [1., -1.] {
+                    let angle = sign * STEP * k as f64;
+                    let moved = self.swing(*branch, angle);
+                    if !self.keeps_backbone(*branch, &moved) {
+                        continue;
+                    }
+                    let after = self.cost(grid, *branch, &moved);
+                    // prefer small swings and small branches on ties
+                    let gain = before - after - 1e-6 * (k * branch.len()) as f64;
+                    if gain > 0. && best.is_none_or(|(_, _, g)| gain > g) {
+                        best = Some((idx, angle, gain));
+                    }
+                }
+            }
+        }
+
+        best
+    }
+
+    fn run(&mut self, bbld: f64) {
+        for _ in 0..MAX_MOVES {
+            let grid = Grid::new(&self.points, bbld);
+            let swing = self
+                .collisions(&grid)
+                .into_iter()
+                .find_map(|(a, b)| self.best_swing(&grid, a, b));
+
+            let Some((idx, angle, _)) = swing else {
+                break;
+            };
+            let branch = self.branches[idx];
+            let moved = self.swing(branch, angle);
+            self.points[branch.start..=branch.end].copy_from_slice(&moved);
+        }
+    }
+}
+
+/// swings helices around their first pair until bubbles of different
+/// branches stop overlapping or nothing helps anymore;
+/// pair_list has to be the nested one the bubbles were gathered from
+pub fn declutter(bblv: &mut BubbleVec, pair_list: &[Option<usize>], bblr: f64) {
+    let lookup = bblv.pos_lookup();
+    let bbld = 2. * bblr;
+
+    let mut declutter = Declutter {
+        points: lookup.iter().map(|&idx| bblv[idx].point).collect(),
+        limit: OVERLAP * bbld,
+        max_bond: MAX_STRETCH * bbld,
+        branches: branches(pair_list),
+    };
+    declutter.run(bbld);
+
+    for (pos, &idx) in lookup.iter().enumerate() {
+        bblv.bubbles[idx].point = declutter.points[pos];
+    }
+    bblv.recalculate_bounds();
+}
+
+/// number of bubble pairs, not neighbours in sequence, that overlap
+pub fn count_overlaps(bblv: &BubbleVec, bblr: f64) -> usize {
+    let lookup = bblv.pos_lookup();
+    let points: Vec<Point> = lookup.iter().map(|&idx| bblv[idx].point).collect();
+    let declutter = Declutter {
+        limit: OVERLAP * 2. * bblr,
+        max_bond: MAX_STRETCH * 2. * bblr,
+        branches: vec![],
+        points,
+    };
+    declutter
+        .collisions(&Grid::new(&declutter.points, 2. * bblr))
+        .len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::draw::gather_bubbles;
+    use crate::forest;
+    use crate::rnamanip::{get_pair_list, read_sequence};
+
+    /// multiloop whose branches carry wide multiloops of their own
+    fn crowded() -> String {
+        let arm = "((((((((...((((((....))))))..((((((....))))))..((((((....))))))...))))))))";
+        format!("..(((({arm}{arm}{arm}{arm}))))..")
+    }
+
+    #[test]
+    fn declutter_removes_overlaps() {
+        let sst = crowded();
+        let pair_list = get_pair_list(&sst).unwrap();
+        let seq = read_sequence(&"G".repeat(sst.len())).unwrap();
+        let tree = forest::grow_tree(&pair_list);
+        let mut bblv = gather_bubbles(&tree, &seq, 0.5, 0.);
+
+        let before = count_overlaps(&bblv, 0.5);
+        assert!(before > 0, "test structure should start out cluttered");
+        declutter(&mut bblv, &pair_list, 0.5);
+        assert_eq!(count_overlaps(&bblv, 0.5), 0);
+
+        // stems are moved as a whole so pairs still touch
+        let lookup = bblv.pos_lookup();
+        for (i, pair) in pair_list.iter().enumerate() {
+            if let Some(j) = *pair {
+                let d = bblv[lookup[i]].point.distance(bblv[lookup[j]].point);
+                assert!((d - 1.).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn declutter_keeps_the_backbone() {
+        let sst = crowded();
+        let pair_list = get_pair_list(&sst).unwrap();
+        let seq = read_sequence(&"G".repeat(sst.len())).unwrap();
+        let tree = forest::grow_tree(&pair_list);
+        let mut bblv = gather_bubbles(&tree, &seq, 0.5, 0.);
+        let lookup = bblv.pos_lookup();
+        let bonds = |bblv: &BubbleVec| -> Vec<f64> {
+            lookup
+                .windows(2)
+                .map(|w| bblv[w[0]].point.distance(bblv[w[1]].point))
+                .collect()
+        };
+
+        let before = bonds(&bblv);
+        declutter(&mut bblv, &pair_list, 0.5);
+        let after = bonds(&bblv);
+        assert!(after.iter().all(|&d| d <= MAX_STRETCH + 1e-9));
+        // only the bonds hanging branches on their loops may change
+        let hinges: Vec<usize> = branches(&pair_list)
+            .iter()
+            .flat_map(|b| [b.start - 1, b.end])
+            .collect();
+        for (pos, (b, a)) in before.iter().zip(&after).enumerate() {
+            if !hinges.contains(&pos) {
+                assert!((a - b).abs() < 1e-9, "bond {pos} went from {b} to {a}");
+            }
+        }
+    }
+
+    #[test]
+    fn helices_and_hinges() {
+        let pair_list = get_pair_list("(((..((...))..((...)).)))").unwrap();
+        let found: Vec<(usize, usize)> = branches(&pair_list)
+            .iter()
+            .map(|b| (b.start, b.end))
+            .collect();
+        assert_eq!(found, vec![(5, 11), (14, 20)]);
+    }
+}
@@ -23,7 +23,7 @@ pub struct Bubble {
 }
 
 impl Bubble {
-    fn new(point: Point, nt: Nucleotide, pos: usize) -> Self {
+    pub(crate) fn new(point: Point, nt: Nucleotide, pos: usize) -> Self {
         Bubble { point, nt, pos }
     }
 
@@ -140,7 +140,7 @@ impl Index<usize> for BubbleVec {
 }
 
 impl BubbleVec {
-    fn new(sp0: Point, sp1: Point) -> Self {
+    pub(crate) fn new(sp0: Point, sp1: Point) -> Self {
         Self {
             bubbles: vec![],
             upper_bounds: Point::new(f64::NEG_INFINITY, f64::NEG_INFINITY),
@@ -154,7 +154,7 @@ impl BubbleVec {
         self.bubbles.len()
     }
 
-    fn push(&mut self, bbl: Bubble) {
+    pub(crate) fn push(&mut self, bbl: Bubble) {
         self.upper_bounds = self.upper_bounds.max(bbl.point);
         self.lower_bounds = self.lower_bounds.min(bbl.point);
         self.bubbles.push(bbl);
@@ -171,6 +171,26 @@ impl BubbleVec {
         self.bubbles[idx].point = p;
     }
 
+    /// bounds have to be refreshed after bubbles are moved around
+    pub fn recalculate_bounds(&mut self) {
+        self.upper_bounds = Point::new(f64::NEG_INFINITY, f64::NEG_INFINITY);
+        self.lower_bounds = Point::new(f64::INFINITY, f64::INFINITY);
+        for bbl in &self.bubbles {
+            self.upper_bounds = self.upper_bounds.max(bbl.point);
+            self.lower_bounds = self.lower_bounds.min(bbl.point);
+        }
+    }
+
+    /// maps nucleotide position to index of its bubble;
+    /// bubbles are stored in the order they were placed not by position
+    pub fn pos_lookup(&self) -> Vec<usize> {
+        let mut lookup = vec![0; self.bubbles.len()];
+        for (idx, bbl) in self.bubbles.iter().enumerate() {
+            lookup[bbl.pos] = idx;
+        }
+        lookup
+    }
+
     pub fn mirror(&mut self, mirror: Mirror) {
         let (x, y) = match (mirror.x, mirror.y) {
             (false, false) => return,
@@ -203,7 +223,7 @@ pub fn gather_bubbles<T>(
     starting_angle: f64,
 ) -> BubbleVec
 where
-    T: std::ops::Index<usize, Output = Nucleotide>,
+    T: std::ops::Index<usize, Output = Nucleotide> + ?Sized,
 {
     let mut stack = vec![];
 
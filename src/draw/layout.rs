@@ -0,0 +1,116 @@
+//! Layout engines turn the tree of loops into bubble coordinates.
+//! [Skeleton] is rnapkin's own skeleton-circle placement,
+//! [NaView] mimics NAView: straight helices, stacked interior loops
+//! and the exterior loop laid out on a flat line.
+mod naview;
+
+use std::str::FromStr;
+
+use super::gather::{gather_bubbles, BubbleVec};
+use crate::forest::{DotBracket, Tree};
+use crate::rnamanip::Nucleotide;
+
+pub use naview::NaView;
+
+pub trait Layout {
+    /// places every nucleotide of the tree;
+    /// bblr is the bubble radius, paired bubbles end up 2 * bblr apart
+    fn layout(&self, tree: &Tree<DotBracket>, seq: &[Nucleotide], bblr: f64) -> BubbleVec;
+}
+
+/// loops are circles (skeletons) bubbles rest upon; see [gather_bubbles]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Skeleton {
+    /// starting angle in radians
+    pub angle: f64,
+}
+
+impl Layout for Skeleton {
+    fn layout(&self, tree: &Tree<DotBracket>, seq: &[Nucleotide], bblr: f64) -> BubbleVec {
+        gather_bubbles(tree, seq, bblr, self.angle)
+    }
+}
+
+/// Available layout engines
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutKind {
+    #[default]
+    Skeleton,
+    NaView,
+}
+
+impl LayoutKind {
+    /// engine rotated by angle in radians
+    pub fn engine(self, angle: f64) -> Box<dyn Layout> {
+        match self {
+            Self::Skeleton => Box::new(Skeleton { angle }),
+            Self::NaView => Box::new(NaView { angle }),
+        }
+    }
+}
+
+impl FromStr for LayoutKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skeleton" | "skelly" => Ok(Self::Skeleton),
+            "naview" => Ok(Self::NaView),
+            _ => Err(format!("unknown layout: {s}; try skeleton or naview")),
+        }
+    }
+}
+
+/// recovers the pair list the tree was grown from
+pub(crate) fn tree_to_pair_list(tree: &Tree<DotBracket>) -> Vec<Option<usize>> {
+    let mut pairs = vec![];
+    for idx in tree.iter() {
+        let val = &tree[idx].val;
+        if let Some(pos) = val.pos {
+            pairs.push((pos, val.pair));
+        }
+    }
+
+    let len = pairs
+        .iter()
+        .map(|&(pos, pair)| pos.max(pair.unwrap_or(0)) + 1)
+        .max()
+        .unwrap_or(0);
+    let mut pair_list = vec![None; len];
+    for (pos, pair) in pairs {
+        if let Some(pair) = pair {
+            pair_list[pos] = Some(pair);
+            pair_list[pair] = Some(pos);
+        }
+    }
+    pair_list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forest::grow_tree;
+    use crate::rnamanip::get_pair_list;
+
+    #[test]
+    fn pair_list_survives_the_tree() {
+        for sst in ["((..((...))..))..(((...)))", "..((...)).", "((((....))))"] {
+            let pair_list = get_pair_list(sst).unwrap();
+            assert_eq!(tree_to_pair_list(&grow_tree(&pair_list)), pair_list);
+        }
+    }
+
+    #[test]
+    fn every_engine_places_every_bubble() {
+        let sst = "..((((...((...))..((....))))))...((...)).";
+        let pair_list = get_pair_list(sst).unwrap();
+        let seq = vec![Nucleotide::G; sst.len()];
+        let tree = grow_tree(&pair_list);
+        for kind in [LayoutKind::Skeleton, LayoutKind::NaView] {
+            let bblv = kind.engine(0.).layout(&tree, &seq, 0.5);
+            let mut positions: Vec<usize> = bblv.bubbles.iter().map(|b| b.pos).collect();
+            positions.sort();
+            assert_eq!(positions, (0..sst.len()).collect::<Vec<_>>());
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! NAView-like layout after Bruccoleri & Heinrich (1988):
+//! - exterior loop is a flat line with its helices standing upright on it
+//! - helices are straight; interior loops and bulges don't bend them,
+//!   unpaired nts bow out on the sides of the stem instead
+//! - hairpins and multiloops are regular polygons with one edge per nt step
+//!   and helices leaving them radially
+use std::f64::consts::{PI, TAU};
+
+use super::{tree_to_pair_list, Layout};
+use crate::draw::gather::{Bubble, BubbleVec};
+use crate::draw::point::Point;
+use crate::forest::{DotBracket, Tree};
+use crate::rnamanip::Nucleotide;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NaView {
+    /// starting angle in radians
+    pub angle: f64,
+}
+
+fn scale(p: Point, by: f64) -> Point {
+    Point::new(p.x * by, p.y * by)
+}
+
+fn unit(p: Point) -> Point {
+    scale(p, 1. / p.length())
+}
+
+/// pair (i, j) sitting at (pi, pj) with the helix growing along dir
+struct Stem {
+    i: usize,
+    j: usize,
+    pi: Point,
+    pj: Point,
+    dir: Point,
+}
+
+/// places count points between p0 and p1 (exclusive) on a circular arc
+/// so that neighbours are bbld apart; bulges to the side of toward.
+/// Falls back to a straight line if the chord is long enough already
+fn arc(p0: Point, p1: Point, count: usize, bbld: f64, toward: Point) -> Vec<Point> {
+    let chord = p0.distance(p1);
+    let steps = (count + 1) as f64;
+
+    if steps * bbld <= chord * 1.0001 {
+        let step = scale(p1 - p0, 1. / steps);
+        return (1..=count).map(|t| p0 + scale(step, t as f64)).collect();
+    }
+
+    // every step is a chord of angle phi: sin(steps * phi / 2) / sin(phi / 2) = chord / bbld
+    // left side falls from steps to 0 as phi goes from 0 to TAU / steps
+    let ratio = chord / bbld;
+    let (mut lo, mut hi) = (1e-9, TAU / steps);
+    for _ in 0..100 {
+        let phi = (lo + hi) / 2.;
+        if (steps * phi / 2.).sin() / (phi / 2.).sin() > ratio {
+            lo = phi;
+        } else {
+            hi = phi;
+        }
+    }
+    let phi = (lo + hi) / 2.;
+    let theta = steps * phi;
+    let radius = bbld / (2. * (phi / 2.).sin());
+    let sagitta = radius * (1. - (theta / 2.).cos());
+
+    let mut normal = unit(Point::new(-(p1 - p0).y, (p1 - p0).x));
+    if normal.x * toward.x + normal.y * toward.y < 0. {
+        normal = scale(normal, -1.);
+    }
+    let middle = p0.get_middle(p1);
+    let center = middle + scale(normal, sagitta - radius);
+    let apex = middle + scale(normal, sagitta);
+
+    let sign = match p0.rotate_around_origin(center, theta / 2.).distance(apex) < 1e-6 * radius {
+        true => 1.,
+        false => -1.,
+    };
+
+    (1..=count)
+        .map(|t| p0.rotate_around_origin(center, sign * theta * t as f64 / steps))
+        .collect()
+}
+
+impl NaView {
+    /// positions of every nt indexed by position
+    fn place(pair_list: &[Option<usize>], bbld: f64) -> Vec<Point> {
+        let n = pair_list.len();
+        let mut points = vec![Point::default(); n];
+        let mut stems = vec![];
+
+        // exterior loop: flat line, helices stand on it
+        let up = Point::new(0., 1.);
+        let mut x = 0.;
+        let mut pos = 0;
+        while pos < n {
+            points[pos] = Point::new(x, 0.);
+            match pair_list[pos] {
+                Some(j) if j > pos => {
+                    points[j] = Point::new(x + bbld, 0.);
+                    stems.push(Stem {
+                        i: pos,
+                        j,
+                        pi: points[pos],
+                        pj: points[j],
+                        dir: up,
+                    });
+                    x += 2. * bbld;
+                    pos = j + 1;
+                }
+                _ => {
+                    x += bbld;
+                    pos += 1;
+                }
+            }
+        }
+
+        while let Some(mut stem) = stems.pop() {
+            // walk up the stacked pairs
+            while stem.i + 1 < stem.j && pair_list[stem.i + 1] == Some(stem.j - 1) {
+                stem.i += 1;
+                stem.j -= 1;
+                stem.pi = stem.pi + scale(stem.dir, bbld);
+                stem.pj = stem.pj + scale(stem.dir, bbld);
+                points[stem.i] = stem.pi;
+                points[stem.j] = stem.pj;
+            }
+
+            let Stem { i, j, pi, pj, dir } = stem;
+            let mut branches = vec![];
+            let mut k = i + 1;
+            while k < j {
+                match pair_list[k] {
+                    Some(l) if l > k => {
+                        branches.push((k, l));
+                        k = l + 1;
+                    }
+                    _ => k += 1,
+                }
+            }
+
+            if let [(k, l)] = branches[..] {
+                // interior loop or bulge; inner helix carries on straight
+                let (left, right) = (k - i - 1, j - l - 1);
+                let rise = bbld * (0.6 * (left.max(right) + 1) as f64).max(1.);
+                let pk = pi + scale(dir, rise);
+                let pl = pj + scale(dir, rise);
+                let outward = pi - pj;
+                for (t, p) in arc(pi, pk, left, bbld, outward).into_iter().enumerate() {
+                    points[i + 1 + t] = p;
+                }
+                for (t, p) in arc(pl, pj, right, bbld, scale(outward, -1.))
+                    .into_iter()
+                    .enumerate()
+                {
+                    points[l + 1 + t] = p;
+                }
+                points[k] = pk;
+                points[l] = pl;
+                stems.push(Stem {
+                    i: k,
+                    j: l,
+                    pi: pk,
+                    pj: pl,
+                    dir,
+                });
+                continue;
+            }
+
+            // hairpin or multiloop: regular polygon with one corner per nt
+            let corners = j - i + 1 - branches.iter().map(|(k, l)| l - k - 1).sum::<usize>();
+            let slice = TAU / corners as f64;
+            let radius = bbld / (2. * (PI / corners as f64).sin());
+            let apothem = (radius * radius - bbld * bbld / 4.).max(0.).sqrt();
+            let center = pi.get_middle(pj) + scale(dir, apothem);
+
+            // step around the polygon starting at pi going away from pj
+            let sign = match pi.rotate_around_origin(center, slice).distance(pj) < bbld * 0.5 {
+                true => -1.,
+                false => 1.,
+            };
+            let corner = |t: usize| pi.rotate_around_origin(center, sign * slice * t as f64);
+
+            let mut t = 1;
+            let mut k = i + 1;
+            while k < j {
+                match pair_list[k] {
+                    Some(l) if l > k => {
+                        let (pk, pl) = (corner(t), corner(t + 1));
+                        points[k] = pk;
+                        points[l] = pl;
+                        stems.push(Stem {
+                            i: k,
+                            j: l,
+                            pi: pk,
+                            pj: pl,
+                            dir: unit(pk.get_middle(pl) - center),
+                        });
+                        t += 2;
+                        k = l + 1;
+                    }
+                    _ => {
+                        points[k] = corner(t);
+                        t += 1;
+                        k += 1;
+                    }
+                }
+            }
+        }
+
+        points
+    }
+}
+
+impl Layout for NaView {
+    fn layout(&self, tree: &Tree<DotBracket>, seq: &[Nucleotide], bblr: f64) -> BubbleVec {
+        let bbld = 2. * bblr;
+        let pair_list = tree_to_pair_list(tree);
+        let points = Self::place(&pair_list, bbld);
+
+        // 5' and 3' labels sit on the exterior line right past the ends
+        let aside = Point::new(bbld, 0.);
+        let (sp0, sp1) = match (points.first(), points.last()) {
+            (Some(&first), Some(&last)) => (first - aside, last + aside),
+            _ => (Point::default() - aside, aside),
+        };
+        let mut bblv = BubbleVec::new(sp0.rotate(self.angle), sp1.rotate(self.angle));
+        for (pos, point) in points.into_iter().enumerate() {
+            bblv.push(Bubble::new(point.rotate(self.angle), seq[pos], pos));
+        }
+        bblv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rnamanip::get_pair_list;
+
+    #[test]
+    fn neighbours_and_pairs_keep_their_distance() {
+        let sst = "..((((...((...))..((....))))))...((..(((....)))...))..";
+        let pair_list = get_pair_list(sst).unwrap();
+        let points = NaView::place(&pair_list, 1.);
+
+        for (i, pair) in pair_list.iter().enumerate() {
+            if let Some(j) = *pair {
+                assert!((points[i].distance(points[j]) - 1.).abs() < 1e-6);
+            }
+        }
+        for w in points.windows(2) {
+            assert!(w[0].distance(w[1]) > 0.99);
+        }
+    }
+
+    #[test]
+    fn interior_loop_keeps_helix_straight() {
+        let pair_list = get_pair_list("((..((....))...))").unwrap();
+        let points = NaView::place(&pair_list, 1.);
+        // both helices stand upright on the exterior line
+        assert!((points[0].x - points[4].x).abs() < 1e-9);
+        assert!((points[16].x - points[11].x).abs() < 1e-9);
+        assert!(points[4].y > points[1].y);
+    }
+}
@@ -0,0 +1,188 @@
+//! Legend box explaining what the highlight colors stand for.
+//! It gets a column of its own at the side of the canvas
+//! so it never covers the molecule.
+use std::str::FromStr;
+
+use anyhow::Result;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+
+use super::colors::ColorTheme;
+use super::output::text_width;
+use super::plot::Extras;
+
+/// Corner of the canvas the legend goes to
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    #[default]
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl FromStr for Corner {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tl" | "top-left" => Ok(Self::TopLeft),
+            "tr" | "top-right" => Ok(Self::TopRight),
+            "bl" | "bottom-left" => Ok(Self::BottomLeft),
+            "br" | "bottom-right" => Ok(Self::BottomRight),
+            _ => Err(format!("unknown corner: {s}; try tl, tr, bl or br")),
+        }
+    }
+}
+
+impl Corner {
+    fn left(&self) -> bool {
+        matches!(self, Self::TopLeft | Self::BottomLeft)
+    }
+
+    fn top(&self) -> bool {
+        matches!(self, Self::TopLeft | Self::TopRight)
+    }
+}
+
+/// highlight colors with what they mean
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Legend {
+    pub entries: Vec<(RGBColor, String)>,
+    pub corner: Corner,
+}
+
+/// row height, padding and the gap between swatch and text in font sizes
+const ROW: f64 = 1.6;
+const PAD: f64 = 0.8;
+const GAP: f64 = 0.6;
+
+impl Legend {
+    /// same color and text given twice shows up once
+    pub fn push(&mut self, color: RGBColor, text: String) {
+        if !self.entries.contains(&(color, text.clone())) {
+            self.entries.push((color, text));
+        }
+    }
+
+    /// font shrinks if there are too many entries to fit the height
+    fn font(&self, font: f64, height: u32) -> f64 {
+        let rows = 2. * PAD + ROW * self.entries.len() as f64 + 1.;
+        font.min(height as f64 / rows)
+    }
+
+    /// box size in pixels
+    fn size(&self, font: f64) -> (f64, f64) {
+        let text = self
+            .entries
+            .iter()
+            .map(|(_, text)| text_width(text, font))
+            .fold(0., f64::max);
+        // swatch, a gap and the text
+        let width = (2. * PAD + 1. + GAP) * font + text;
+        let height = 2. * PAD + ROW * self.entries.len() as f64;
+        (width, height * font)
+    }
+}
+
+/// width of the column at the side of the canvas taken by the legend
+pub(crate) fn legend_column(legend: &Option<Legend>, font: f64, height: u32) -> u32 {
+    match legend {
+        Some(legend) if !legend.entries.is_empty() => {
+            let font = legend.font(font, height);
+            (legend.size(font).0 + font).ceil() as u32
+        }
+        _ => 0,
+    }
+}
+
+/// draws the legend into its column and hands back the rest of the canvas
+pub(crate) fn with_legend<D: DrawingBackend>(
+    root: DrawingArea<D, Shift>,
+    extras: &Extras,
+    column: u32,
+    font: f64,
+    theme: &ColorTheme,
+) -> Result<DrawingArea<D, Shift>> {
+    let legend = match &extras.legend {
+        Some(legend) if column > 0 => legend,
+        _ => return Ok(root),
+    };
+    let (width, height) = root.dim_in_pixel();
+    let (rest, aside) = match legend.corner.left() {
+        true => {
+            let (aside, rest) = root.split_horizontally(column);
+            (rest, aside)
+        }
+        false => root.split_horizontally(width - column),
+    };
+
+    let font = legend.font(font, height);
+    let (box_width, box_height) = legend.size(font);
+    let x0 = match legend.corner.left() {
+        true => 0.5 * font,
+        false => column as f64 - 0.5 * font - box_width,
+    };
+    let y0 = match legend.corner.top() {
+        true => 0.5 * font,
+        false => height as f64 - 0.5 * font - box_height,
+    };
+    aside
+        .draw(&Rectangle::new(
+            [
+                (x0 as i32, y0 as i32),
+                ((x0 + box_width) as i32, (y0 + box_height) as i32),
+            ],
+            theme.fg.stroke_width(1),
+        ))
+        .unwrap();
+
+    let style = TextStyle::from(("sans-serif", font).into_font())
+        .pos(Pos::new(HPos::Left, VPos::Center))
+        .color(&theme.fg);
+    for (row, (color, text)) in legend.entries.iter().enumerate() {
+        let y = y0 + (PAD + ROW * (row as f64 + 0.5)) * font;
+        let swatch = (x0 + (PAD + 0.5) * font, y);
+        aside
+            .draw(&Circle::new(
+                (swatch.0 as i32, swatch.1 as i32),
+                (0.5 * font) as i32,
+                color.filled(),
+            ))
+            .unwrap();
+        let at = ((x0 + (PAD + 1. + GAP) * font) as i32, y as i32);
+        aside
+            .draw(&Text::new(text.clone(), at, style.clone()))
+            .unwrap();
+    }
+    Ok(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn column_fits_the_box() {
+        let mut legend = Legend::default();
+        assert_eq!(legend_column(&Some(legend.clone()), 10., 500), 0);
+        legend.push(RED, "terminator".to_owned());
+        legend.push(RED, "terminator".to_owned());
+        legend.push(BLUE, "P1".to_owned());
+        assert_eq!(legend.entries.len(), 2);
+
+        // padding, swatch and gap, "terminator" and the outer margin
+        assert_eq!(legend_column(&Some(legend.clone()), 10., 500), 102);
+        // too many rows for the height make the font smaller
+        assert!(legend_column(&Some(legend), 10., 40) < 102);
+        assert_eq!(legend_column(&None, 10., 500), 0);
+    }
+
+    #[test]
+    fn corner_from_str() {
+        assert_eq!("br".parse::<Corner>(), Ok(Corner::BottomRight));
+        assert_eq!("top-left".parse::<Corner>(), Ok(Corner::TopLeft));
+        assert!("middle".parse::<Corner>().is_err());
+    }
+}
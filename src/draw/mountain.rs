@@ -0,0 +1,186 @@
+//! Mountain plot: position on x, number of pairs enclosing it on y.
+//! Several structures share the axes which makes comparing
+//! e.g. predicted and reference structures quick.
+use std::path::Path;
+
+use anyhow::Result;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+
+use super::colors::ColorTheme;
+use super::output::{caption_strip, draw_caption, render, Painter};
+
+/// height of every position: how many pairs (i, j) have i <= pos <= j;
+/// crossing pairs count just like nested ones
+pub fn mountain(pair_list: &[Option<usize>]) -> Vec<usize> {
+    let mut height = 0;
+    let mut heights = Vec::with_capacity(pair_list.len());
+    for (pos, pair) in pair_list.iter().enumerate() {
+        match pair {
+            Some(pair) if *pair > pos => {
+                height += 1;
+                heights.push(height);
+            }
+            Some(_) => {
+                heights.push(height);
+                height -= 1;
+            }
+            None => heights.push(height),
+        }
+    }
+    heights
+}
+
+/// named mountain drawn as one line of the plot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Track {
+    pub name: String,
+    pub heights: Vec<usize>,
+}
+
+impl Track {
+    pub fn new(name: impl Into<String>, pair_list: &[Option<usize>]) -> Self {
+        Self {
+            name: name.into(),
+            heights: mountain(pair_list),
+        }
+    }
+}
+
+struct Mountains<'a> {
+    tracks: &'a [Track],
+    theme: &'a ColorTheme,
+    caption: &'a Option<String>,
+    size: (u32, u32),
+    font: f64,
+    strip: u32,
+}
+
+impl Mountains<'_> {
+    /// tracks take the highlight colors in turn
+    fn color(&self, idx: usize) -> RGBColor {
+        match idx {
+            0 => self.theme.fg,
+            n => self.theme.highlights[(n - 1) % self.theme.highlights.len()],
+        }
+    }
+}
+
+impl Painter for Mountains<'_> {
+    fn size(&self) -> (u32, u32) {
+        (self.size.0, self.size.1 + self.strip)
+    }
+
+    fn paint<D: DrawingBackend>(&self, root: DrawingArea<D, Shift>) -> Result<()> {
+        root.fill(&self.theme.bg).unwrap();
+        let (upper, lower) = root.split_vertically(self.size.1);
+        let fg = &self.theme.fg;
+        let font = ("sans-serif", self.font).into_font().color(fg);
+
+        let len = self
+            .tracks
+            .iter()
+            .map(|t| t.heights.len())
+            .max()
+            .unwrap_or(0);
+        let top = self
+            .tracks
+            .iter()
+            .flat_map(|t| t.heights.iter().copied())
+            .max()
+            .unwrap_or(0);
+
+        let mut chart = ChartBuilder::on(&upper)
+            .margin(self.font as u32)
+            .x_label_area_size(2. * self.font)
+            .y_label_area_size(3. * self.font)
+            .build_cartesian_2d(1..len.max(2), 0..top + 1)
+            .unwrap();
+
+        chart
+            .configure_mesh()
+            .disable_mesh()
+            .axis_style(fg)
+            .label_style(font.clone())
+            .x_desc("position")
+            .y_desc("enclosing pairs")
+            .draw()
+            .unwrap();
+
+        for (idx, track) in self.tracks.iter().enumerate() {
+            let color = self.color(idx);
+            let width = (self.font / 8.).max(1.) as u32;
+            let points = track.heights.iter().enumerate().map(|(i, h)| (i + 1, *h));
+            chart
+                .draw_series(LineSeries::new(points, color.stroke_width(width)))
+                .unwrap()
+                .label(track.name.clone())
+                .legend(move |(x, y)| {
+                    PathElement::new([(x, y), (x + 20, y)], color.stroke_width(width))
+                });
+        }
+
+        if self.tracks.len() > 1 {
+            chart
+                .configure_series_labels()
+                .position(SeriesLabelPosition::UpperRight)
+                .background_style(self.theme.bg)
+                .border_style(fg)
+                .label_font(font)
+                .draw()
+                .unwrap();
+        }
+
+        if let Some(caption) = self.caption {
+            draw_caption(&lower, self.font, self.theme, caption)?;
+        }
+        Ok(())
+    }
+}
+
+/// draws every track onto the same axes; canvas is twice as wide as it is high
+pub fn plot_mountain<P: AsRef<Path>>(
+    tracks: &[Track],
+    filename: &P,
+    theme: &ColorTheme,
+    height: u32,
+    caption: &Option<String>,
+) -> Result<Option<String>> {
+    let font = (height as f64 / 40.).max(10.);
+    let mountains = Mountains {
+        tracks,
+        theme,
+        caption,
+        size: (2 * height, height),
+        font,
+        strip: caption_strip(caption, font),
+    };
+    render(&mountains, filename)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rnamanip::get_pair_list;
+
+    #[test]
+    fn mountain_heights() {
+        let pair_list = get_pair_list("((..)).(.)").unwrap();
+        assert_eq!(mountain(&pair_list), vec![1, 2, 2, 2, 2, 1, 0, 1, 1, 1]);
+        let knotted = get_pair_list("([)]").unwrap();
+        assert_eq!(mountain(&knotted), vec![1, 2, 2, 1]);
+        assert!(mountain(&[]).is_empty());
+    }
+
+    #[test]
+    fn mountain_plot_svg() {
+        let tracks = [
+            Track::new("reference", &get_pair_list("((((....))))").unwrap()),
+            Track::new("predicted", &get_pair_list(".(((....))).").unwrap()),
+        ];
+        let svg = plot_mountain(&tracks, &"o.x", &ColorTheme::dark(), 300, &None)
+            .unwrap()
+            .expect("svg string");
+        assert!(svg.contains("reference") && svg.contains("predicted"));
+    }
+}
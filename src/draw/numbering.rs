@@ -0,0 +1,150 @@
+//! Position labels placed every so often along the sequence.
+//! Each label sticks out of its bubble along the normal of the backbone,
+//! on whichever side has more room, so it lands outside the structure.
+use std::str::FromStr;
+
+use super::gather::BubbleVec;
+use super::Point;
+
+/// Which positions get labeled and what number the first nt has
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Numbering {
+    /// label every n-th nucleotide
+    pub every: usize,
+    /// number of the first nucleotide; 1 unless matching some other coordinates
+    pub offset: i64,
+}
+
+impl Default for Numbering {
+    fn default() -> Self {
+        Self {
+            every: 10,
+            offset: 1,
+        }
+    }
+}
+
+impl FromStr for Numbering {
+    type Err = String;
+
+    /// just the interval; offset is set separately
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<usize>() {
+            Ok(every) if every > 0 => Ok(Self {
+                every,
+                ..Self::default()
+            }),
+            _ => Err(format!(
+                "numbering interval should be a positive integer, got {s}"
+            )),
+        }
+    }
+}
+
+/// label ready to be drawn; all in layout coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Label {
+    /// start of the tick, at the edge of the bubble
+    pub tick0: Point,
+    /// end of the tick
+    pub tick1: Point,
+    /// center of the text
+    pub at: Point,
+    pub text: String,
+}
+
+/// distance to the closest bubble that isn't the one at idx
+fn clearance(bblv: &BubbleVec, idx: usize, p: Point) -> f64 {
+    bblv.bubbles
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != idx)
+        .map(|(_, bbl)| bbl.point.distance(p))
+        .fold(f64::INFINITY, f64::min)
+}
+
+pub(crate) fn labels(bblv: &BubbleVec, bblr: f64, numbering: Numbering) -> Vec<Label> {
+    let lookup = bblv.pos_lookup();
+    let len = lookup.len();
+    let mut labels = vec![];
+
+    for (pos, &idx) in lookup.iter().enumerate() {
+        let number = pos as i64 + numbering.offset;
+        if number.rem_euclid(numbering.every as i64) != 0 {
+            continue;
+        }
+
+        let point = bblv[idx].point;
+        let prev = bblv[lookup[pos.saturating_sub(1)]].point;
+        let next = bblv[lookup[(pos + 1).min(len - 1)]].point;
+        let tangent = next - prev;
+        let norm = tangent.length();
+        let normal = match norm > 0. {
+            true => Point::new(-tangent.y / norm, tangent.x / norm),
+            false => Point::new(0., 1.), // lonely nt, any direction will do
+        };
+
+        let along = |sign: f64, dist: f64| {
+            Point::new(
+                point.x + sign * normal.x * dist,
+                point.y + sign * normal.y * dist,
+            )
+        };
+        let probe = 2. * bblr;
+        let sign = match clearance(bblv, idx, along(1., probe))
+            >= clearance(bblv, idx, along(-1., probe))
+        {
+            true => 1.,
+            false => -1.,
+        };
+
+        labels.push(Label {
+            tick0: along(sign, 1.1 * bblr),
+            tick1: along(sign, 1.7 * bblr),
+            at: along(sign, 2.6 * bblr),
+            text: number.to_string(),
+        });
+    }
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::draw::gather_bubbles;
+    use crate::forest;
+    use crate::rnamanip::{get_pair_list, Nucleotide};
+
+    #[test]
+    fn labels_stay_clear_of_bubbles() {
+        let pair_list = get_pair_list("..((((((....))))))....((((....))))..").unwrap();
+        let seq = vec![Nucleotide::X; pair_list.len()];
+        let bblv = gather_bubbles(&forest::grow_tree(&pair_list), &seq, 0.5, 0.);
+
+        let labeled = labels(&bblv, 0.5, Numbering::default());
+        let texts: Vec<&str> = labeled.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(texts, ["10", "20", "30"]);
+        for label in &labeled {
+            // text center is clear of every bubble
+            assert!(bblv
+                .bubbles
+                .iter()
+                .all(|b| b.point.distance(label.at) > 0.9));
+        }
+
+        let shifted = Numbering {
+            every: 5,
+            offset: 1001,
+        };
+        let labeled = labels(&bblv, 0.5, shifted);
+        assert_eq!(labeled[0].text, "1005");
+        assert_eq!(labeled.len(), 7);
+    }
+
+    #[test]
+    fn interval_from_str() {
+        assert_eq!("25".parse::<Numbering>().unwrap().every, 25);
+        assert!("0".parse::<Numbering>().is_err());
+        assert!("ten".parse::<Numbering>().is_err());
+    }
+}
@@ -0,0 +1,274 @@
+//! Bits shared by every kind of plot: picking the backend by extension,
+//! the caption strip and stroking curves in the requested line style
+use std::ffi::OsStr;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+use plotters::coord::Shift;
+use plotters::element::DashedPathElement;
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+use svg2pdf::usvg::{self, fontdb};
+use svg2pdf::{ConversionOptions, PageOptions};
+
+use super::colormap::{colorbar_strip, draw_colorbar};
+use super::colors::ColorTheme;
+use super::plot::{Extras, LineStyle};
+use super::Point;
+
+/// Something that knows how to paint itself on a blank canvas
+pub(crate) trait Painter {
+    /// canvas size in pixels
+    fn size(&self) -> (u32, u32);
+    fn paint<D: DrawingBackend>(&self, root: DrawingArea<D, Shift>) -> Result<()>;
+}
+
+/// presents exactly once when everything is drawn;
+/// svg backend ignores whatever is drawn after the first present
+fn paint<D: DrawingBackend, T: Painter>(painter: &T, root: DrawingArea<D, Shift>) -> Result<()> {
+    painter.paint(root.clone())?;
+    root.present().unwrap();
+    Ok(())
+}
+
+fn svg_string<T: Painter>(painter: &T) -> Result<String> {
+    let mut svgstring = String::with_capacity(0x10000);
+    {
+        let root = SVGBackend::with_string(&mut svgstring, painter.size()).into_drawing_area();
+        paint(painter, root)?;
+    }
+    Ok(svgstring)
+}
+
+/// generic families plotters asks for mapped onto fonts that are actually installed
+fn fallback_families(fonts: &mut fontdb::Database) {
+    let installed = |family: &str| {
+        fonts
+            .faces()
+            .any(|face| face.families.iter().any(|(name, _)| name == family))
+    };
+    let pick = |candidates: &[&str]| -> Option<String> {
+        let family = candidates.iter().find(|&&family| installed(family))?;
+        Some(family.to_string())
+    };
+
+    let sans = pick(&[
+        "Arial",
+        "Helvetica",
+        "DejaVu Sans",
+        "Liberation Sans",
+        "Noto Sans",
+    ]);
+    let serif = pick(&[
+        "Times New Roman",
+        "DejaVu Serif",
+        "Liberation Serif",
+        "Noto Serif",
+    ]);
+    let mono = pick(&[
+        "Courier New",
+        "DejaVu Sans Mono",
+        "Liberation Mono",
+        "Noto Sans Mono",
+    ]);
+    if let Some(family) = sans {
+        fonts.set_sans_serif_family(family);
+    }
+    if let Some(family) = serif {
+        fonts.set_serif_family(family);
+    }
+    if let Some(family) = mono {
+        fonts.set_monospace_family(family);
+    }
+}
+
+/// converts the svg into pdf; subsets of the fonts used are embedded
+/// so the letters look the same wherever the pdf is opened
+/// and the text can still be selected and searched
+fn svg_to_pdf(svg: &str) -> Result<Vec<u8>> {
+    let mut options = usvg::Options::default();
+    let fonts = options.fontdb_mut();
+    fonts.load_system_fonts();
+    if fonts.is_empty() {
+        bail!("pdf: no fonts found on this system to embed");
+    }
+    fallback_families(fonts);
+
+    let tree = usvg::Tree::from_str(svg, &options)?;
+    svg2pdf::to_pdf(&tree, ConversionOptions::default(), PageOptions::default())
+        .map_err(|err| anyhow!("pdf: {err}"))
+}
+
+/// picks backend by the extension: .svg, .png, .pdf or .x
+/// which returns the svg as a string instead of writing it down
+pub(crate) fn render<P: AsRef<Path>, T: Painter>(
+    painter: &T,
+    filename: &P,
+) -> Result<Option<String>> {
+    let size = painter.size();
+    match filename.as_ref().extension().and_then(OsStr::to_str) {
+        Some("svg") => paint(painter, SVGBackend::new(filename, size).into_drawing_area())?,
+        Some("png") => paint(
+            painter,
+            BitMapBackend::new(filename, size).into_drawing_area(),
+        )?,
+        Some("pdf") => std::fs::write(filename, svg_to_pdf(&svg_string(painter)?)?)?,
+        Some("x") => return Ok(Some(svg_string(painter)?)),
+        _ => panic!("correct extension should be determined beforehand"),
+    };
+    Ok(None)
+}
+
+/// rough width of the text in pixels; sans-serif glyphs are about 0.6 em wide
+pub(crate) fn text_width(text: &str, font: f64) -> f64 {
+    0.6 * font * text.chars().count() as f64
+}
+
+/// height of the strip left under the plot for a caption
+pub(crate) fn caption_strip(caption: &Option<String>, font: f64) -> u32 {
+    match caption {
+        Some(_) => (3. * font).round() as u32,
+        None => 0,
+    }
+}
+
+/// font size that fits the caption into the strip below the plot
+fn caption_font_size(caption: &str, font: f64, width: u32) -> f64 {
+    let fits = (width as f64 - 2. * font) / text_width(caption, 1.);
+    (1.1 * font).min(fits)
+}
+
+/// caption gets its own strip under the plot
+pub(crate) fn draw_caption<D: DrawingBackend>(
+    strip: &DrawingArea<D, Shift>,
+    font: f64,
+    theme: &ColorTheme,
+    caption: &str,
+) -> Result<()> {
+    let (width, height) = strip.dim_in_pixel();
+    let style =
+        TextStyle::from(("sans-serif", caption_font_size(caption, font, width)).into_font())
+            .pos(Pos::new(HPos::Left, VPos::Center))
+            .color(&theme.fg);
+    strip
+        .draw(&Text::new(
+            caption.to_owned(),
+            (font.round() as i32, height as i32 / 2),
+            style,
+        ))
+        .unwrap();
+    Ok(())
+}
+
+/// height of the strip under the plot holding the caption and the colorbar
+pub(crate) fn bottom_strip(extras: &Extras, font: f64) -> u32 {
+    caption_strip(&extras.caption, font).max(colorbar_strip(&extras.data, font))
+}
+
+/// caption on the left, colorbar on the right; each gets half if both are there
+pub(crate) fn draw_bottom<D: DrawingBackend>(
+    strip: &DrawingArea<D, Shift>,
+    font: f64,
+    theme: &ColorTheme,
+    extras: &Extras,
+) -> Result<()> {
+    match (&extras.caption, &extras.data) {
+        (Some(caption), Some(data)) => {
+            let (left, right) = strip.split_horizontally(strip.dim_in_pixel().0 / 2);
+            draw_caption(&left, font, theme, caption)?;
+            draw_colorbar(&right, font, theme, data)?;
+        }
+        (Some(caption), None) => draw_caption(strip, font, theme, caption)?,
+        (None, Some(data)) => draw_colorbar(strip, font, theme, data)?,
+        (None, None) => (),
+    }
+    Ok(())
+}
+
+/// samples quadratic bezier curve going from p0 to p1 bent towards ctrl
+pub(crate) fn bezier(p0: Point, ctrl: Point, p1: Point, samples: usize) -> Vec<(f64, f64)> {
+    (0..=samples)
+        .map(|i| {
+            let t = i as f64 / samples as f64;
+            let u = 1. - t;
+            let x = u * u * p0.x + 2. * u * t * ctrl.x + t * t * p1.x;
+            let y = u * u * p0.y + 2. * u * t * ctrl.y + t * t * p1.y;
+            (x, y)
+        })
+        .collect()
+}
+
+/// draws path with dashes scaled to the stroke width
+pub(crate) fn stroke<DB, CT>(
+    root: &DrawingArea<DB, CT>,
+    path: Vec<(f64, f64)>,
+    color: &RGBColor,
+    width: f64,
+    line: LineStyle,
+) where
+    DB: DrawingBackend,
+    CT: CoordTranslate<From = (f64, f64)>,
+{
+    let style = ShapeStyle::from(color).stroke_width(width.max(1.) as u32);
+    match line {
+        LineStyle::Solid => root.draw(&PathElement::new(path, style)).unwrap(),
+        LineStyle::Dashed => root
+            .draw(&DashedPathElement::new(path, width * 3., width * 2., style))
+            .unwrap(),
+        LineStyle::Dotted => root
+            .draw(&DashedPathElement::new(path, width, width * 1.5, style))
+            .unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Captioned;
+
+    impl Painter for Captioned {
+        fn size(&self) -> (u32, u32) {
+            (200, 100)
+        }
+
+        fn paint<D: DrawingBackend>(&self, root: DrawingArea<D, Shift>) -> Result<()> {
+            let (upper, lower) = root.split_vertically(70);
+            upper.fill(&WHITE).unwrap();
+            draw_caption(&lower, 10., &ColorTheme::dark(), "caption")
+        }
+    }
+
+    #[test]
+    fn svg_file_keeps_everything_drawn() {
+        let path = std::env::temp_dir().join(format!("rnapkin_{}.svg", std::process::id()));
+        render(&Captioned, &path).unwrap();
+        let svg = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(svg.contains("caption"));
+    }
+
+    #[test]
+    fn pdf_file() {
+        let path = std::env::temp_dir().join(format!("rnapkin_{}.pdf", std::process::id()));
+        let mut fonts = fontdb::Database::new();
+        fonts.load_system_fonts();
+        if fonts.is_empty() {
+            // nothing to embed; refused rather than written without the letters
+            let err = render(&Captioned, &path).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "pdf: no fonts found on this system to embed"
+            );
+            return;
+        }
+
+        render(&Captioned, &path).unwrap();
+        let pdf = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+        // the caption is set in an embedded font
+        assert!(pdf.windows(5).any(|w| w == b"/Font"));
+        assert!(pdf.windows(9).any(|w| w == b"/FontFile"));
+    }
+}
@@ -1,18 +1,28 @@
 use std::borrow::Borrow;
-use std::ffi::OsStr;
+use std::ops::Range;
 use std::path::Path;
+use std::str::FromStr;
 
+use super::callout::{place, Callout, Region, TEXT};
+use super::colormap::DataTrack;
 use super::colors::ColorTheme;
 use super::gather::BubbleVec;
+use super::legend::{legend_column, with_legend, Legend};
+use super::numbering::{labels, Label, Numbering};
+use super::output::{bezier, bottom_strip, draw_bottom, render, stroke, Painter};
+use super::title::{title_strip, with_title, Title};
 use super::Point;
+use crate::rnamanip::PairKind;
 
 use anyhow::Result;
 use plotters::coord::types::RangedCoordf64;
+use plotters::coord::Shift;
 use plotters::prelude::*;
 use plotters::style::text_anchor::{HPos, Pos, VPos};
 
 /// Indicates if and along which axes
 /// to mirror points
+#[derive(Debug, Default, Clone, Copy)]
 pub struct Mirror {
     pub x: bool,
     pub y: bool,
@@ -24,11 +34,237 @@ impl Mirror {
     }
 }
 
+/// How pseudoknot connectors are stroked
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+impl FromStr for LineStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "solid" => Ok(Self::Solid),
+            "dashed" => Ok(Self::Dashed),
+            "dotted" => Ok(Self::Dotted),
+            _ => Err(format!(
+                "unknown line style: {s}; try solid, dashed or dotted"
+            )),
+        }
+    }
+}
+
+/// Everything drawn besides the bubbles themselves
+#[derive(Debug, Default, Clone)]
+pub struct Extras {
+    /// per position highlight ring color
+    pub highlights: Vec<Option<RGBColor>>,
+    /// crossing pairs left out of the layout; (5' pos, 3' pos)
+    pub knots: Vec<(usize, usize)>,
+    pub knot_style: LineStyle,
+    /// short text in the bottom left corner e.g. free energy
+    pub caption: Option<String>,
+    /// pairs that get a bond symbol between their bubbles; (5' pos, 3' pos)
+    pub bonds: Vec<(usize, usize)>,
+    /// connect consecutive nts with a line
+    pub backbone: bool,
+    /// position labels every so often
+    pub numbering: Option<Numbering>,
+    /// colors bubbles by data instead of by nucleotide
+    pub data: Option<DataTrack>,
+    /// paired bubbles keep their nucleotide color and data colors
+    /// the bond symbols instead; napkin only
+    pub data_on_bonds: bool,
+    /// named parts of the molecule labeled on the drawing; napkin only
+    pub regions: Vec<Region>,
+    /// box explaining the highlight colors
+    pub legend: Option<Legend>,
+    /// title and subtitle in a strip above or below the drawing
+    pub title: Option<Title>,
+}
+
+impl Extras {
+    /// nucleotide color unless bubbles are colored by data
+    pub(crate) fn bubble_color(&self, pos: usize, nt: &RGBColor, theme: &ColorTheme) -> RGBColor {
+        match &self.data {
+            Some(data) => data.color(pos, theme),
+            None => *nt,
+        }
+    }
+
+    /// bubbles shrink a bit if anything is drawn in between them
+    fn bubble_scale(&self) -> f64 {
+        match self.backbone || !self.bonds.is_empty() {
+            true => 0.7,
+            false => 1.,
+        }
+    }
+}
+
+/// bond symbols are only collected for nested pairs, knots have connectors of their own
+pub fn bonds(pair_list: &[Option<usize>]) -> Vec<(usize, usize)> {
+    pair_list
+        .iter()
+        .enumerate()
+        .filter_map(|(pos, pair)| pair.filter(|&pair| pair > pos).map(|pair| (pos, pair)))
+        .collect()
+}
+
+/// draws curved connectors between pseudoknotted bubbles;
+/// curves bow away from the middle of the molecule so they don't
+/// cut through it more than they have to
+fn draw_knots<D: DrawingBackend>(
+    root: &DrawingArea<D, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+    bblv: &BubbleVec,
+    radius: f64,
+    theme: &ColorTheme,
+    extras: &Extras,
+) -> Result<()> {
+    if extras.knots.is_empty() {
+        return Ok(());
+    }
+
+    let lookup = bblv.pos_lookup();
+    let center = bblv.upper_bounds.get_middle(bblv.lower_bounds);
+    let width = (radius * 0.3).max(1.);
+
+    for &(pos, pair) in &extras.knots {
+        let p0 = bblv[lookup[pos]].point;
+        let p1 = bblv[lookup[pair]].point;
+        let mid = p0.get_middle(p1);
+        let delta = p1 - p0;
+        let mut normal = Point::new(-delta.y, delta.x);
+        if (mid.x + normal.x - center.x).powi(2) + (mid.y + normal.y - center.y).powi(2)
+            < (mid.x - normal.x - center.x).powi(2) + (mid.y - normal.y - center.y).powi(2)
+        {
+            normal = Point::new(delta.y, -delta.x);
+        }
+        let ctrl = Point::new(mid.x + normal.x * 0.3, mid.y + normal.y * 0.3);
+        let curve = bezier(p0, ctrl, p1, 64);
+        stroke(root, curve, &theme.knot, width, extras.knot_style);
+    }
+    Ok(())
+}
+
+/// straight lines through consecutive bubbles;
+/// drawn first so the bubbles cover the middle of every segment
+fn draw_backbone<D: DrawingBackend>(
+    root: &DrawingArea<D, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+    bblv: &BubbleVec,
+    radius: f64,
+    theme: &ColorTheme,
+) -> Result<()> {
+    let lookup = bblv.pos_lookup();
+    let path: Vec<(f64, f64)> = lookup
+        .iter()
+        .map(|&idx| (bblv[idx].point.x, bblv[idx].point.y))
+        .collect();
+    let width = (radius * 0.15).max(1.);
+    stroke(root, path, &theme.backbone, width, LineStyle::Solid);
+    Ok(())
+}
+
+/// line for Watson-Crick pairs, dot for GU and open circle for the rest;
+/// the symbols sit in the gap left by the shrunken bubbles
+fn draw_bonds<D: DrawingBackend>(
+    root: &DrawingArea<D, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+    bblv: &BubbleVec,
+    radius: f64,
+    theme: &ColorTheme,
+    extras: &Extras,
+) -> Result<()> {
+    let lookup = bblv.pos_lookup();
+    // colored bonds have to be thick enough for the color to show
+    let width = match extras.data_on_bonds {
+        true => (radius * 0.35).max(2.),
+        false => (radius * 0.15).max(1.),
+    };
+
+    for &(pos, pair) in &extras.bonds {
+        let color = match (&extras.data, extras.data_on_bonds) {
+            (Some(data), true) => data.color(pos, theme),
+            _ => theme.bond,
+        };
+        let style = Into::<ShapeStyle>::into(color);
+        let (bbl0, bbl1) = (&bblv[lookup[pos]], &bblv[lookup[pair]]);
+        let (p0, p1) = (bbl0.point, bbl1.point);
+        let mid = p0.get_middle(p1);
+        match PairKind::new(bbl0.nt, bbl1.nt) {
+            PairKind::WatsonCrick => {
+                let path = vec![(p0.x, p0.y), (p1.x, p1.y)];
+                stroke(root, path, &color, width, LineStyle::Solid);
+            }
+            PairKind::Wobble => {
+                let dot = Circle::new((mid.x, mid.y), radius * 0.18, style.filled());
+                root.draw(&dot).unwrap();
+            }
+            PairKind::NonCanonical => {
+                let ring = Circle::new(
+                    (mid.x, mid.y),
+                    radius * 0.25,
+                    style.stroke_width(width as u32),
+                );
+                root.draw(&ring).unwrap();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// numbers with a short tick pointing at their bubble
+fn draw_numbering<D: DrawingBackend>(
+    root: &DrawingArea<D, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+    labels: &[Label],
+    radius: f64,
+    theme: &ColorTheme,
+) -> Result<()> {
+    let width = (radius * 0.1).max(1.);
+    for label in labels {
+        let tick = vec![
+            (label.tick0.x, label.tick0.y),
+            (label.tick1.x, label.tick1.y),
+        ];
+        stroke(root, tick, &theme.fg, width, LineStyle::Solid);
+        let style = TextStyle::from(("sans-serif", 0.9 * radius).into_font())
+            .pos(Pos::new(HPos::Center, VPos::Center))
+            .color(&theme.fg);
+        let text = Text::new(label.text.clone(), (label.at.x, label.at.y), style);
+        root.draw(&text).unwrap();
+    }
+    Ok(())
+}
+
+/// region names tied to their regions by leader lines
+fn draw_callouts<D: DrawingBackend>(
+    root: &DrawingArea<D, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+    callouts: &[Callout],
+    radius: f64,
+    theme: &ColorTheme,
+) -> Result<()> {
+    let width = (radius * 0.15).max(1.);
+    for callout in callouts {
+        let (p0, p1) = callout.leader;
+        let leader = vec![(p0.x, p0.y), (p1.x, p1.y)];
+        stroke(root, leader, &callout.color, width, LineStyle::Solid);
+        let style = TextStyle::from(("sans-serif", TEXT * radius).into_font())
+            .pos(Pos::new(HPos::Center, VPos::Center))
+            .color(&theme.fg);
+        let text = Text::new(callout.text.clone(), (callout.at.x, callout.at.y), style);
+        root.draw(&text).unwrap();
+    }
+    Ok(())
+}
+
 fn nucleotide_bubble<C, D, S>(
     coords: Point,
     radius: f64,
     letter: S,
     bbl_clr: &C,
+    letter_clr: &RGBColor,
     drawing_area: &DrawingArea<D, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
 ) -> Result<()>
 where
@@ -40,7 +276,7 @@ where
     let c = Circle::new((0, 0), radius, Into::<ShapeStyle>::into(bbl_clr).filled());
     let style = TextStyle::from(("mono", 0.8 * radius).into_font())
         .pos(pos)
-        .color(&BLACK);
+        .color(letter_clr);
     let text = Text::new(letter, (0, 0), style);
     let ee = EmptyElement::at((coords.x, coords.y)) + c + text;
     drawing_area.draw(&ee).unwrap(); // Cant "?", because there is extremely cursed lifetime on the error
@@ -53,7 +289,7 @@ fn highlighted_bubble<C, D, S>(
     letter: S,
     bbl_clr: &C,
     hhl_clr: &C,
-    bg_clr: &RGBAColor,
+    theme: &ColorTheme,
     drawing_area: &DrawingArea<D, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
 ) -> Result<()>
 where
@@ -66,7 +302,7 @@ where
     let bc = Circle::new(
         (0, 0),
         radius * 0.8,
-        Into::<ShapeStyle>::into(bg_clr).filled(),
+        Into::<ShapeStyle>::into(theme.bg).filled(),
     );
     let c = Circle::new(
         (0, 0),
@@ -75,7 +311,7 @@ where
     );
     let style = TextStyle::from(("mono", 0.8 * radius).into_font())
         .pos(pos)
-        .color(&BLACK);
+        .color(&theme.letter);
 
     let text = Text::new(letter, (0, 0), style);
     let ee = EmptyElement::at((coords.x, coords.y)) + nc + bc + c + text;
@@ -123,28 +359,49 @@ fn draw<D: DrawingBackend>(
     bblv: &BubbleVec,
     radius: f64,
     theme: &ColorTheme,
-    highlights: &[Option<usize>],
+    extras: &Extras,
+    labels: &[Label],
+    callouts: &[Callout],
 ) -> Result<()> {
+    if extras.backbone {
+        draw_backbone(root, bblv, radius, theme)?;
+    }
+    draw_bonds(root, bblv, radius, theme, extras)?;
+    draw_knots(root, bblv, radius, theme, extras)?;
+
+    let mut bonded = vec![false; bblv.bubbles.len()];
+    if extras.data_on_bonds {
+        for &(pos, pair) in &extras.bonds {
+            (bonded[pos], bonded[pair]) = (true, true);
+        }
+    }
+
+    let ends_radius = radius;
+    let radius = radius * extras.bubble_scale();
     for bbl in &bblv.bubbles {
         let (letter, bubble_color) = bbl.nt.extract_text_and_color(theme);
-        if let Some(highlight_index) = highlights[bbl.pos] {
-            let highlight_color = theme.highlights[highlight_index];
+        let bubble_color = &match bonded[bbl.pos] {
+            true => *bubble_color,
+            false => extras.bubble_color(bbl.pos, bubble_color, theme),
+        };
+        if let Some(&Some(highlight_color)) = extras.highlights.get(bbl.pos) {
             highlighted_bubble(
                 bbl.point,
                 radius,
                 letter,
                 bubble_color,
                 &highlight_color,
-                &theme.bg,
+                theme,
                 root,
             )?;
         } else {
-            nucleotide_bubble(bbl.point, radius, letter, bubble_color, root)?;
+            nucleotide_bubble(bbl.point, radius, letter, bubble_color, &theme.letter, root)?;
         }
     }
 
-    draw_ends(root, bblv, radius, theme)?;
-    root.present().unwrap();
+    draw_numbering(root, labels, ends_radius, theme)?;
+    draw_callouts(root, callouts, ends_radius, theme)?;
+    draw_ends(root, bblv, ends_radius, theme)?;
     Ok(())
 }
 
@@ -157,7 +414,7 @@ fn simple_draw<D: DrawingBackend>(
 ) -> Result<()> {
     for bbl in &bblv.bubbles {
         let (letter, bubble_color) = bbl.nt.extract_text_and_color(theme);
-        nucleotide_bubble(bbl.point, radius, letter, bubble_color, root)?;
+        nucleotide_bubble(bbl.point, radius, letter, bubble_color, &theme.letter, root)?;
     }
     draw_ends(root, bblv, radius, theme)?;
     root.present().unwrap();
@@ -167,8 +424,7 @@ fn simple_draw<D: DrawingBackend>(
 fn calculate_coords(
     upper_bounds: Point,
     lower_bounds: Point,
-    x: i32,
-    y: i32,
+    pixels: (Range<i32>, Range<i32>),
     margin: f64,
     mirror: Mirror,
 ) -> Cartesian2d<RangedCoordf64, RangedCoordf64> {
@@ -184,7 +440,71 @@ fn calculate_coords(
         (upper_bounds.y + margin)..(lower_bounds.y - margin)
     };
 
-    Cartesian2d::<RangedCoordf64, RangedCoordf64>::new(xrange, yrange, (0..x, 0..y))
+    Cartesian2d::<RangedCoordf64, RangedCoordf64>::new(xrange, yrange, pixels)
+}
+
+/// the classic napkin: bubbles laid out by one of the layout engines
+struct Napkin<'a> {
+    bblv: &'a BubbleVec,
+    labels: Vec<Label>,
+    callouts: Vec<Callout>,
+    /// bubbles and labels fit in between
+    upper_bounds: Point,
+    lower_bounds: Point,
+    radius: f64,
+    margin: f64,
+    theme: &'a ColorTheme,
+    mirror: Mirror,
+    extras: &'a Extras,
+    size: (u32, u32),
+    strip: u32,
+    column: u32,
+    title: u32,
+}
+
+/// caption and colorbar stay readable even if the bubbles are tiny
+const MIN_FONT: f64 = 8.;
+
+impl Napkin<'_> {
+    fn font(&self) -> f64 {
+        self.radius.max(MIN_FONT)
+    }
+}
+
+impl Painter for Napkin<'_> {
+    fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// fills the canvas and draws the molecule onto it
+    /// leaving a strip at the bottom for the caption if there's one
+    fn paint<D: DrawingBackend>(&self, root: DrawingArea<D, Shift>) -> Result<()> {
+        root.fill(&self.theme.bg).unwrap();
+        let height = self.size.1 - self.strip - self.title;
+        let root = with_title(root, self.extras, self.title, height, self.theme)?;
+        let root = with_legend(root, self.extras, self.column, self.font(), self.theme)?;
+        let (_, why) = root.dim_in_pixel();
+        let (upper, lower) = root.split_vertically(why - self.strip);
+        // pixel ranges are absolute; the title or legend may come first
+        let upper = upper.apply_coord_spec(calculate_coords(
+            self.upper_bounds,
+            self.lower_bounds,
+            upper.get_pixel_range(),
+            self.margin,
+            self.mirror,
+        ));
+        draw(
+            &upper,
+            self.bblv,
+            self.radius,
+            self.theme,
+            self.extras,
+            &self.labels,
+            &self.callouts,
+        )?;
+        draw_bottom(&lower, self.font(), self.theme, self.extras)?;
+        Ok(())
+    }
 }
 
 pub fn plot<P: AsRef<Path>>(
@@ -194,13 +514,27 @@ pub fn plot<P: AsRef<Path>>(
     theme: &ColorTheme,
     height: u32,
     mirror: Mirror,
-    highlights: &[Option<usize>],
+    extras: &Extras,
 ) -> Result<Option<String>> {
-    let (dx, dy) = get_distance(bblv.upper_bounds, bblv.lower_bounds);
+    let labels = match extras.numbering {
+        Some(numbering) => labels(bblv, bblr, numbering),
+        None => vec![],
+    };
+    let numbers: Vec<Point> = labels.iter().map(|label| label.at).collect();
+    let callouts = place(bblv, bblr, &extras.regions, &numbers);
+    let (upper_bounds, lower_bounds) = numbers
+        .iter()
+        .copied()
+        .chain(callouts.iter().flat_map(Callout::corners))
+        .fold(
+            (bblv.upper_bounds, bblv.lower_bounds),
+            |(upper, lower), p| (upper.max(p), lower.min(p)),
+        );
+
+    let (dx, dy) = get_distance(upper_bounds, lower_bounds);
     let xyratio = dx / dy;
 
     let xsize = (xyratio * height as f64).round() as u32;
-    let (ex, why) = (xsize, height);
 
     let margin = bblr * 2.2;
 
@@ -209,52 +543,62 @@ pub fn plot<P: AsRef<Path>>(
     // looks slightly better with 0.99 than 1, pairs overlap less
     let radius = xsize as f64 * bblr * 0.99 / (dx + margin);
 
-    match filename.as_ref().extension().and_then(OsStr::to_str) {
-        Some("svg") => {
-            let root = SVGBackend::new(filename, (ex, why)).into_drawing_area();
-            let root = root.apply_coord_spec(calculate_coords(
-                bblv.upper_bounds,
-                bblv.lower_bounds,
-                ex as i32,
-                why as i32,
-                margin,
-                mirror,
-            ));
-            root.fill(&theme.bg)?;
-            draw(&root, bblv, radius, theme, highlights)?;
-        }
-        Some("png") => {
-            let root = BitMapBackend::new(filename, (ex, why)).into_drawing_area();
-            let root = root.apply_coord_spec(calculate_coords(
-                bblv.upper_bounds,
-                bblv.lower_bounds,
-                ex as i32,
-                why as i32,
-                margin,
-                mirror,
-            ));
-            root.fill(&theme.bg)?;
-            draw(&root, bblv, radius, theme, highlights)?;
-        }
-        Some("x") => {
-            let mut svgstring = String::with_capacity(0x10000);
-            {
-                let root = SVGBackend::with_string(&mut svgstring, (ex, why)).into_drawing_area();
-                let root = root.apply_coord_spec(calculate_coords(
-                    bblv.upper_bounds,
-                    bblv.lower_bounds,
-                    ex as i32,
-                    why as i32,
-                    margin,
-                    mirror,
-                ));
-                root.fill(&theme.bg)?;
-                draw(&root, bblv, radius, theme, highlights)?
-            }
-            return Ok(Some(svgstring));
-        }
-        _ => panic!("correct extension should be determined beforehand"),
+    let strip = bottom_strip(extras, radius.max(MIN_FONT));
+    let column = legend_column(&extras.legend, radius.max(MIN_FONT), height + strip);
+    let title = title_strip(extras, height);
+    let napkin = Napkin {
+        bblv,
+        labels,
+        callouts,
+        upper_bounds,
+        lower_bounds,
+        radius,
+        margin,
+        theme,
+        mirror,
+        extras,
+        size: (xsize + column, height + strip + title),
+        strip,
+        column,
+        title,
     };
+    render(&napkin, filename)
+}
 
-    Ok(None)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::draw::gather_bubbles;
+    use crate::forest;
+    use crate::rnamanip::{get_pair_list, read_sequence};
+
+    #[test]
+    fn bond_symbols() {
+        // G-C line, G-U dot, A-G circle
+        let pair_list = get_pair_list("(((....)))").unwrap();
+        assert_eq!(bonds(&pair_list), vec![(0, 9), (1, 8), (2, 7)]);
+        let seq = read_sequence("GGAAAAAGUC").unwrap();
+        let tree = forest::grow_tree(&pair_list);
+        let bblv = gather_bubbles(&tree, &seq, 0.5, 0.);
+
+        let extras = Extras {
+            bonds: bonds(&pair_list),
+            backbone: true,
+            ..Extras::default()
+        };
+        let svg = plot(
+            &bblv,
+            0.5,
+            &"o.x",
+            &ColorTheme::dark(),
+            300,
+            Mirror::default(),
+            &extras,
+        )
+        .unwrap()
+        .expect("svg string");
+        // backbone + one bond line; bubbles + dot + ring
+        assert_eq!(svg.matches("<polyline").count(), 2);
+        assert_eq!(svg.matches("<circle").count(), 12);
+    }
 }
@@ -29,6 +29,15 @@ impl Point {
         Self::new(xo, yo)
     }
 
+    /// distance from the origin
+    pub fn length(self) -> f64 {
+        self.x.hypot(self.y)
+    }
+
+    pub fn distance(self, other: Self) -> f64 {
+        (self - other).length()
+    }
+
     pub fn max(self, other: Self) -> Self {
         Self::new(self.x.max(other.x), self.y.max(other.y))
     }
@@ -87,5 +96,8 @@ mod tests {
         let orbiter = p.rotate_around_origin(Point::new(4., 8.), PI);
         assert!(roughly_equal(orbiter.x, 6.));
         assert!(roughly_equal(orbiter.y, 12.));
+
+        assert!(roughly_equal(Point::new(3., -4.).length(), 5.));
+        assert!(roughly_equal(p.distance(Point::new(5., 0.)), 5.));
     }
 }
@@ -0,0 +1,214 @@
+//! Title with an optional subtitle in a strip of its own
+//! above or below the drawing, e.g. the record name with its source and energy.
+use std::str::FromStr;
+
+use anyhow::Result;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+
+use super::colors::ColorTheme;
+use super::output::text_width;
+use super::plot::Extras;
+
+/// Where the title strip goes and how the text is aligned in it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    TopLeft,
+    #[default]
+    Top,
+    TopRight,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl FromStr for Position {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "top-left" | "tl" => Ok(Self::TopLeft),
+            "top" => Ok(Self::Top),
+            "top-right" | "tr" => Ok(Self::TopRight),
+            "bottom-left" | "bl" => Ok(Self::BottomLeft),
+            "bottom" => Ok(Self::Bottom),
+            "bottom-right" | "br" => Ok(Self::BottomRight),
+            _ => Err(format!(
+                "unknown title position: {s}; try top, bottom, top-left, bottom-right..."
+            )),
+        }
+    }
+}
+
+impl Position {
+    fn top(&self) -> bool {
+        matches!(self, Self::TopLeft | Self::Top | Self::TopRight)
+    }
+
+    fn hpos(&self) -> HPos {
+        match self {
+            Self::TopLeft | Self::BottomLeft => HPos::Left,
+            Self::Top | Self::Bottom => HPos::Center,
+            Self::TopRight | Self::BottomRight => HPos::Right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Title {
+    pub text: String,
+    /// smaller line under the title
+    pub subtitle: Option<String>,
+    /// font family
+    pub font: String,
+    /// title font size in pixels; scales with the drawing if not given
+    pub size: Option<f64>,
+    pub position: Position,
+}
+
+impl Default for Title {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            subtitle: None,
+            font: "sans-serif".to_owned(),
+            size: None,
+            position: Position::default(),
+        }
+    }
+}
+
+/// subtitle is this much smaller than the title
+const SUBTITLE: f64 = 0.6;
+
+impl Title {
+    /// height is that of the drawing itself, without any strips
+    fn font_size(&self, height: u32) -> f64 {
+        self.size.unwrap_or((height as f64 / 30.).max(12.))
+    }
+}
+
+/// height of the strip taken by the title and the subtitle
+pub(crate) fn title_strip(extras: &Extras, height: u32) -> u32 {
+    let title = match &extras.title {
+        Some(title) => title,
+        None => return 0,
+    };
+    let font = title.font_size(height);
+    let lines = match title.subtitle {
+        Some(_) => 1.5 + 1.5 * SUBTITLE,
+        None => 1.5,
+    };
+    ((lines + 0.5) * font).round() as u32
+}
+
+/// draws the title into its strip and hands back the rest of the canvas
+pub(crate) fn with_title<D: DrawingBackend>(
+    root: DrawingArea<D, Shift>,
+    extras: &Extras,
+    strip: u32,
+    height: u32,
+    theme: &ColorTheme,
+) -> Result<DrawingArea<D, Shift>> {
+    let title = match &extras.title {
+        Some(title) if strip > 0 => title,
+        _ => return Ok(root),
+    };
+    let (width, total) = root.dim_in_pixel();
+    let (area, rest) = match title.position.top() {
+        true => root.split_vertically(strip),
+        false => {
+            let (rest, area) = root.split_vertically(total - strip);
+            (area, rest)
+        }
+    };
+
+    let font = title.font_size(height);
+    let margin = font;
+    let x = match title.position.hpos() {
+        HPos::Left => margin,
+        HPos::Center => width as f64 / 2.,
+        HPos::Right => width as f64 - margin,
+    };
+    // long lines shrink to fit
+    let fit = |text: &str, size: f64| {
+        let fits = (width as f64 - 2. * margin) / text_width(text, 1.);
+        size.min(fits)
+    };
+    let line = |text: &str, size: f64, y: f64| {
+        let style = TextStyle::from((title.font.as_str(), fit(text, size)).into_font())
+            .pos(Pos::new(title.position.hpos(), VPos::Center))
+            .color(&theme.fg);
+        area.draw(&Text::new(text.to_owned(), (x as i32, y as i32), style))
+            .unwrap();
+    };
+
+    // a quarter of the font as padding above the first line
+    let y = font;
+    line(&title.text, font, y);
+    if let Some(subtitle) = &title.subtitle {
+        line(
+            subtitle,
+            SUBTITLE * font,
+            y + 0.75 * font + 0.75 * SUBTITLE * font,
+        );
+    }
+    Ok(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::draw::output::{render, Painter};
+
+    struct Titled(Extras);
+
+    impl Painter for Titled {
+        fn size(&self) -> (u32, u32) {
+            (300, 300 + title_strip(&self.0, 300))
+        }
+
+        fn paint<D: DrawingBackend>(&self, root: DrawingArea<D, Shift>) -> Result<()> {
+            let strip = title_strip(&self.0, 300);
+            let rest = with_title(root, &self.0, strip, 300, &ColorTheme::dark())?;
+            assert_eq!(rest.dim_in_pixel(), (300, 300));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn title_gets_its_own_strip() {
+        let mut extras = Extras::default();
+        assert_eq!(title_strip(&extras, 900), 0);
+
+        extras.title = Some(Title {
+            text: "offsam".to_owned(),
+            position: Position::BottomLeft,
+            ..Title::default()
+        });
+        // 30 px font, a line and a half with some padding
+        assert_eq!(title_strip(&extras, 900), 60);
+
+        if let Some(title) = extras.title.as_mut() {
+            title.subtitle = Some("Rfam RF00162".to_owned());
+        }
+        assert_eq!(title_strip(&extras, 900), 87);
+
+        let svg = render(&Titled(extras), &"o.x")
+            .unwrap()
+            .expect("svg string");
+        assert!(svg.contains("offsam"));
+        assert!(svg.contains("Rfam RF00162"));
+    }
+
+    #[test]
+    fn position_from_str() {
+        assert_eq!(
+            "bottom-right".parse::<Position>(),
+            Ok(Position::BottomRight)
+        );
+        assert_eq!("top".parse::<Position>(), Ok(Position::Top));
+        assert!("left".parse::<Position>().is_err());
+    }
+}
@@ -0,0 +1,183 @@
+//! Errors that may arise while reading user input.
+//! Every variant tied to a particular spot in the input
+//! carries its [Location] so it can be reported like:
+//! "unmatched ')' at line 3 col 17"
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// 1-indexed line and column of the offending character.
+/// When a lone string is parsed (e.g. [crate::rnamanip::get_pair_list])
+/// it counts as line 1 and column is just the position + 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Location {
+    pub fn new(line: usize, col: usize) -> Self {
+        Self { line, col }
+    }
+
+    /// location of 0-indexed position within a single line string
+    pub fn in_string(pos: usize) -> Self {
+        Self::new(1, pos + 1)
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {} col {}", self.line, self.col)
+    }
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    /// closing bracket without the opening one
+    UnmatchedBracket {
+        symbol: char,
+        at: Location,
+    },
+    /// opening bracket that is never closed
+    UnclosedBracket {
+        symbol: char,
+        at: Location,
+    },
+    InvalidNucleotide {
+        symbol: char,
+        at: Location,
+    },
+    /// sequence and secondary structure disagree on the length
+    LengthMismatch {
+        sequence: usize,
+        structure: usize,
+    },
+    /// neither sequence nor secondary structure was found
+    NothingToDraw,
+    /// stdin is a terminal so there's nothing piped in
+    NoInput,
+    /// line that doesn't fit the format e.g. CT row with 4 columns
+    Malformed {
+        format: &'static str,
+        at: Location,
+        reason: String,
+    },
+    /// more pseudoknot layers than there are bracket families
+    TooManyKnotLayers,
+    InvalidUtf8 {
+        line: usize,
+    },
+    Io {
+        path: String,
+        source: io::Error,
+    },
+}
+
+impl ParseError {
+    pub fn malformed(format: &'static str, at: Location, reason: impl Into<String>) -> Self {
+        Self::Malformed {
+            format,
+            at,
+            reason: reason.into(),
+        }
+    }
+
+    /// where in the input the error happened if anywhere in particular
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            Self::UnmatchedBracket { at, .. }
+            | Self::UnclosedBracket { at, .. }
+            | Self::InvalidNucleotide { at, .. }
+            | Self::Malformed { at, .. } => Some(*at),
+            Self::InvalidUtf8 { line } => Some(Location::new(*line, 1)),
+            _ => None,
+        }
+    }
+
+    /// errors from [crate::rnamanip] only know the position within
+    /// a string; this translates it into the real spot in the input
+    pub(crate) fn relocate<F>(self, locate: F) -> Self
+    where
+        F: Fn(usize) -> Location,
+    {
+        match self {
+            Self::UnmatchedBracket { symbol, at } => Self::UnmatchedBracket {
+                symbol,
+                at: locate(at.col - 1),
+            },
+            Self::UnclosedBracket { symbol, at } => Self::UnclosedBracket {
+                symbol,
+                at: locate(at.col - 1),
+            },
+            Self::InvalidNucleotide { symbol, at } => Self::InvalidNucleotide {
+                symbol,
+                at: locate(at.col - 1),
+            },
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnmatchedBracket { symbol, at } => write!(f, "unmatched '{symbol}' at {at}"),
+            Self::UnclosedBracket { symbol, at } => write!(f, "unclosed '{symbol}' at {at}"),
+            Self::InvalidNucleotide { symbol, at } => {
+                write!(f, "invalid nucleotide '{symbol}' at {at}")
+            }
+            Self::LengthMismatch {
+                sequence,
+                structure,
+            } => write!(
+                f,
+                "sequence ({sequence} nt) and structure ({structure} nt) have different lengths"
+            ),
+            Self::NothingToDraw => write!(
+                f,
+                "Neither sequence nor secondary structure found in the input!"
+            ),
+            Self::NoInput => write!(f, "No input provided! nothing to do :c"),
+            Self::Malformed { format, at, reason } => write!(f, "{format}: {reason} at {at}"),
+            Self::TooManyKnotLayers => {
+                write!(
+                    f,
+                    "pseudoknots nested deeper than available bracket families"
+                )
+            }
+            Self::InvalidUtf8 { line } => write!(f, "invalid utf8 at line {line}"),
+            Self::Io { path, source } => write!(f, "could not read {path}: {source}"),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_messages() {
+        let err = ParseError::UnmatchedBracket {
+            symbol: ')',
+            at: Location::new(3, 17),
+        };
+        assert_eq!(err.to_string(), "unmatched ')' at line 3 col 17");
+
+        let relocated = ParseError::InvalidNucleotide {
+            symbol: 'X',
+            at: Location::in_string(4),
+        }
+        .relocate(|pos| Location::new(2, pos + 10));
+        assert_eq!(relocated.location(), Some(Location::new(2, 14)));
+    }
+}
@@ -0,0 +1,116 @@
+//! Predicts secondary structure straight from the sequence
+//! so sequence only input can be drawn without any external software.
+//! Two algorithms are available:
+//! - [nussinov]: plain base-pair maximisation; fast and dumb
+//! - [mfe]: Zuker-style minimum free energy with nearest neighbour
+//!   parameters taken from Turner 2004 (see [turner2004] for what's included)
+//!
+//! [ExternalFolder] hands the job over to a real folding program instead.
+mod external;
+mod nussinov;
+pub mod turner2004;
+mod zuker;
+
+use crate::rnamanip::Nucleotide;
+use std::str::FromStr;
+
+pub use external::{ExternalFolder, FolderError};
+pub use nussinov::nussinov;
+pub use zuker::mfe;
+
+/// hairpin has to have at least this many unpaired nts
+pub const MIN_HAIRPIN: usize = 3;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Nussinov,
+    #[default]
+    Mfe,
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nussinov" => Ok(Self::Nussinov),
+            "mfe" | "zuker" => Ok(Self::Mfe),
+            _ => Err(format!(
+                "unknown folding algorithm: {s}; try mfe or nussinov"
+            )),
+        }
+    }
+}
+
+/// Predicted structure; energy in kcal/mol is only known for [Algorithm::Mfe]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Folded {
+    pub pair_list: Vec<Option<usize>>,
+    pub energy: Option<f64>,
+}
+
+/// folds the sequence with the chosen algorithm
+pub fn fold(sequence: &[Nucleotide], algorithm: Algorithm) -> Folded {
+    match algorithm {
+        Algorithm::Nussinov => Folded {
+            pair_list: nussinov(sequence),
+            energy: None,
+        },
+        Algorithm::Mfe => {
+            let (pair_list, energy) = mfe(sequence);
+            Folded {
+                pair_list,
+                energy: Some(energy),
+            }
+        }
+    }
+}
+
+/// Watson-Crick and GU wobble; unknown nts never pair
+fn can_pair(a: Nucleotide, b: Nucleotide) -> bool {
+    use Nucleotide::*;
+    matches!((a, b), (A, U) | (U, A) | (G, C) | (C, G) | (G, U) | (U, G))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rnamanip::{get_dot_bracket, read_sequence};
+
+    fn fold_to_dot_bracket(seq: &str, algorithm: Algorithm) -> (String, Option<f64>) {
+        let folded = fold(&read_sequence(seq).unwrap(), algorithm);
+        (get_dot_bracket(&folded.pair_list).unwrap(), folded.energy)
+    }
+
+    #[test]
+    fn fold_simple_hairpin() {
+        let (sst, energy) = fold_to_dot_bracket("GGGGAAAACCCC", Algorithm::Mfe);
+        assert_eq!(sst, "((((....))))");
+        assert!((energy.unwrap() - -4.3).abs() < 1e-9);
+
+        let (sst, energy) = fold_to_dot_bracket("GGGGAAAACCCC", Algorithm::Nussinov);
+        assert_eq!(sst, "((((....))))");
+        assert_eq!(energy, None);
+    }
+
+    #[test]
+    fn nothing_to_pair() {
+        let (sst, energy) = fold_to_dot_bracket("AAAAAAAAAA", Algorithm::Mfe);
+        assert_eq!(sst, "..........");
+        assert_eq!(energy, Some(0.));
+        let (sst, _) = fold_to_dot_bracket("GGGAAA", Algorithm::Nussinov);
+        assert_eq!(sst, "......");
+        let (sst, _) = fold_to_dot_bracket("", Algorithm::Mfe);
+        assert_eq!(sst, "");
+    }
+
+    #[test]
+    fn fold_interior_loop() {
+        // two hairpins would pay for two loops; one long helix
+        // with an interior loop in the middle is cheaper
+        let seq = "GGGCGAAAGCCCAAAAGGGCGAAAGCCC";
+        let (sst, energy) = fold_to_dot_bracket(seq, Algorithm::Mfe);
+        assert_eq!(sst, "((((....((((....))))....))))");
+        assert!(energy.unwrap() < 0.);
+    }
+}
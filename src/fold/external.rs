@@ -0,0 +1,299 @@
+//! Runs an external folding program (RNAfold, LinearFold, RNAstructure's Fold ...)
+//! and reads its output with the usual [ParsedInput] machinery.
+//!
+//! The command is split on whitespace. By default the sequence is written to
+//! the program's stdin as FASTA and the structure is read from its stdout.
+//! Programs that only work with files can use placeholders instead:
+//! - `{input}` is replaced with a path to a FASTA file holding the sequence
+//! - `{output}` is replaced with a path the structure is read back from
+//!
+//! e.g. `"RNAfold --noPS"` or `"Fold {input} {output}"`
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::process::{Command, ExitStatus, Stdio};
+use std::str::FromStr;
+use std::thread;
+
+use tempfile::NamedTempFile;
+
+use crate::error::ParseError;
+use crate::utils::{ParsedInput, StructureFormat};
+
+const INPUT: &str = "{input}";
+const OUTPUT: &str = "{output}";
+
+/// External folding program could not be run or its output could not be read.
+/// Unlike [ParseError] it's not about the user's input, though an unreadable
+/// output carries the [ParseError] it ran into
+#[derive(Debug)]
+pub enum FolderError {
+    /// program could not be started or its files written or read
+    Io {
+        command: String,
+        source: io::Error,
+    },
+    /// program exited with an error
+    Failed {
+        command: String,
+        status: ExitStatus,
+        stderr: String,
+    },
+    NotUtf8 {
+        command: String,
+    },
+    Unreadable {
+        command: String,
+        source: ParseError,
+    },
+    /// output was fine but held no structure
+    NoStructure {
+        command: String,
+    },
+}
+
+impl fmt::Display for FolderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { command, source } => write!(f, "folder \"{command}\": {source}"),
+            Self::Failed {
+                command,
+                status,
+                stderr,
+            } => match stderr.is_empty() {
+                true => write!(f, "folder \"{command}\": {status}"),
+                false => write!(f, "folder \"{command}\": {status}; {stderr}"),
+            },
+            Self::NotUtf8 { command } => write!(f, "folder \"{command}\": output is not utf8"),
+            Self::Unreadable { command, source } => {
+                write!(f, "folder \"{command}\": unreadable output; {source}")
+            }
+            Self::NoStructure { command } => {
+                write!(f, "folder \"{command}\": no structure in the output")
+            }
+        }
+    }
+}
+
+impl Error for FolderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Unreadable { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalFolder {
+    program: String,
+    args: Vec<String>,
+}
+
+impl FromStr for ExternalFolder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.split_whitespace().map(str::to_owned);
+        match tokens.next() {
+            Some(program) => Ok(Self {
+                program,
+                args: tokens.collect(),
+            }),
+            None => Err("folder command is empty".to_owned()),
+        }
+    }
+}
+
+/// freshly created file with a random name, removed once dropped;
+/// never follows whatever someone else might have left in the temp dir
+fn temp_file(suffix: &str) -> io::Result<NamedTempFile> {
+    tempfile::Builder::new()
+        .prefix("rnapkin_")
+        .suffix(suffix)
+        .tempfile()
+}
+
+impl ExternalFolder {
+    fn command_line(&self) -> String {
+        std::iter::once(&self.program)
+            .chain(&self.args)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn io_error(&self, source: io::Error) -> FolderError {
+        FolderError::Io {
+            command: self.command_line(),
+            source,
+        }
+    }
+
+    /// folds the sequence and returns the first structure the program printed
+    pub fn fold(&self, name: Option<&str>, sequence: &str) -> Result<ParsedInput, FolderError> {
+        let fasta = format!(">{}\n{sequence}\n", name.unwrap_or("rnapkin"));
+        let wants = |placeholder| self.args.iter().any(|arg| arg.contains(placeholder));
+        let temp = |suffix| temp_file(suffix).map_err(|e| self.io_error(e));
+        let input = wants(INPUT).then(|| temp(".fa")).transpose()?;
+        let output = wants(OUTPUT).then(|| temp(".out")).transpose()?;
+
+        if let Some(input) = &input {
+            input
+                .as_file()
+                .write_all(fasta.as_bytes())
+                .map_err(|e| self.io_error(e))?;
+        }
+
+        let args = self.args.iter().map(|arg| {
+            let mut arg = arg.clone();
+            if let Some(input) = &input {
+                arg = arg.replace(INPUT, &input.path().to_string_lossy());
+            }
+            if let Some(output) = &output {
+                arg = arg.replace(OUTPUT, &output.path().to_string_lossy());
+            }
+            arg
+        });
+
+        let mut child = Command::new(&self.program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| self.io_error(e))?;
+
+        // fed from another thread; a program printing a lot before it reads
+        // would otherwise wait on us to empty its stdout while we wait on its stdin
+        let stdin = child.stdin.take().filter(|_| input.is_none());
+        let writer = stdin.map(|mut stdin| {
+            thread::spawn(move || {
+                // program may exit without reading; broken pipe is not our problem then
+                let _ = stdin.write_all(fasta.as_bytes());
+            })
+        });
+
+        let out = child.wait_with_output().map_err(|e| self.io_error(e))?;
+        if let Some(writer) = writer {
+            let _ = writer.join();
+        }
+        if !out.status.success() {
+            return Err(FolderError::Failed {
+                command: self.command_line(),
+                status: out.status,
+                stderr: String::from_utf8_lossy(&out.stderr).trim().to_owned(),
+            });
+        }
+
+        let text = match &output {
+            Some(output) => fs::read_to_string(output.path()).map_err(|e| self.io_error(e))?,
+            None => String::from_utf8(out.stdout).map_err(|_| FolderError::NotUtf8 {
+                command: self.command_line(),
+            })?,
+        };
+
+        self.read_output(&text)
+    }
+
+    /// first record of the output that has a structure
+    fn read_output(&self, text: &str) -> Result<ParsedInput, FolderError> {
+        let lines: Vec<String> = text.lines().map(str::to_owned).collect();
+        let format = StructureFormat::sniff(&lines);
+        ParsedInput::parse_records(&lines, format)
+            .map_err(|source| FolderError::Unreadable {
+                command: self.command_line(),
+                source,
+            })?
+            .into_iter()
+            .find(|pi| pi.secondary_structure.is_some() || pi.pair_list.is_some())
+            .ok_or_else(|| FolderError::NoStructure {
+                command: self.command_line(),
+            })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// stands in for RNAfold: echoes the FASTA back followed by a fixed structure
+    fn stub(script: &NamedTempFile, body: &str) -> ExternalFolder {
+        fs::write(script.path(), body).unwrap();
+        format!("sh {}", script.path().display()).parse().unwrap()
+    }
+
+    #[test]
+    fn fold_with_stub() {
+        let script = temp_file(".sh").unwrap();
+        let folder = stub(&script, "cat\necho '((((....)))) ( -4.30)'\n");
+        let pi = folder.fold(Some("hairpin"), "GGGGAAAACCCC").unwrap();
+        assert_eq!(pi.rna_name.as_deref(), Some("hairpin"));
+        assert_eq!(pi.sequence.as_deref(), Some("GGGGAAAACCCC"));
+        assert_eq!(pi.secondary_structure.as_deref(), Some("((((....))))"));
+    }
+
+    #[test]
+    fn fold_through_files() {
+        let script = temp_file(".sh").unwrap();
+        let body = "cat /dev/null > \"$2\"\nsed -n 2p \"$1\" >> \"$2\"\necho '(....)' >> \"$2\"\n";
+        fs::write(script.path(), body).unwrap();
+        let command = format!("sh {} {INPUT} {OUTPUT}", script.path().display());
+        let folder: ExternalFolder = command.parse().unwrap();
+        let pi = folder.fold(None, "GAAAAC").unwrap();
+        assert_eq!(pi.secondary_structure.as_deref(), Some("(....)"));
+    }
+
+    #[test]
+    fn chatty_folder_does_not_block() {
+        // fills the stdout pipe before reading anything from stdin
+        let script = temp_file(".sh").unwrap();
+        let body =
+            "head -c 200000 /dev/zero | tr '\\0' '#'\necho\ncat > /dev/null\necho '(....)'\n";
+        let folder = stub(&script, body);
+        let pi = folder.fold(None, &"A".repeat(200_000)).unwrap();
+        assert_eq!(pi.secondary_structure.as_deref(), Some("(....)"));
+    }
+
+    #[test]
+    fn temp_files_are_fresh() {
+        let (a, b) = (temp_file(".fa").unwrap(), temp_file(".fa").unwrap());
+        assert_ne!(a.path(), b.path());
+        assert!(a
+            .path()
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("rnapkin_"));
+    }
+
+    #[test]
+    fn folder_failures() {
+        let script = temp_file(".sh").unwrap();
+        let folder = stub(&script, "echo oops >&2\nexit 3\n");
+        let err = folder.fold(None, "GAAAAC").unwrap_err();
+        assert!(matches!(err, FolderError::Failed { .. }));
+        assert!(err.to_string().ends_with("exit status: 3; oops"));
+
+        let folder = stub(&script, "echo GAAAAC\n");
+        assert!(matches!(
+            folder.fold(None, "GAAAAC"),
+            Err(FolderError::NoStructure { .. })
+        ));
+
+        let folder = stub(&script, "echo '((..'\n");
+        assert!(matches!(
+            folder.fold(None, "GAAAAC"),
+            Err(FolderError::Unreadable { .. })
+        ));
+
+        let missing: ExternalFolder = "surely-not-a-folder-on-path".parse().unwrap();
+        assert!(matches!(
+            missing.fold(None, "GAAAAC"),
+            Err(FolderError::Io { .. })
+        ));
+        assert!("  ".parse::<ExternalFolder>().is_err());
+    }
+}
@@ -0,0 +1,113 @@
+//! Nussinov base-pair maximisation
+use super::{can_pair, MIN_HAIRPIN};
+use crate::rnamanip::Nucleotide;
+
+/// maximises the amount of base pairs; O(n^3)
+pub fn nussinov(seq: &[Nucleotide]) -> Vec<Option<usize>> {
+    let n = seq.len();
+    let mut pair_list = vec![None; n];
+    if n == 0 {
+        return pair_list;
+    }
+
+    // best[i * n + j]: max pairs within i..=j
+    let mut best = vec![0u32; n * n];
+    let get = |best: &[u32], i: usize, j: usize| if i < j { best[i * n + j] } else { 0 };
+
+    for span in MIN_HAIRPIN + 1..n {
+        for i in 0..n - span {
+            let j = i + span;
+            let mut score = get(&best, i + 1, j).max(get(&best, i, j - 1));
+            if can_pair(seq[i], seq[j]) {
+                score = score.max(get(&best, i + 1, j - 1) + 1);
+            }
+            for k in i + 1..j {
+                score = score.max(get(&best, i, k) + get(&best, k + 1, j));
+            }
+            best[i * n + j] = score;
+        }
+    }
+
+    let mut deck = vec![(0, n - 1)];
+    while let Some((i, j)) = deck.pop() {
+        if i >= j || get(&best, i, j) == 0 {
+            continue;
+        }
+        let score = get(&best, i, j);
+        if score == get(&best, i + 1, j) {
+            deck.push((i + 1, j));
+        } else if score == get(&best, i, j - 1) {
+            deck.push((i, j - 1));
+        } else if can_pair(seq[i], seq[j]) && score == get(&best, i + 1, j - 1) + 1 {
+            pair_list[i] = Some(j);
+            pair_list[j] = Some(i);
+            deck.push((i + 1, j - 1));
+        } else {
+            let k = (i + 1..j)
+                .find(|&k| score == get(&best, i, k) + get(&best, k + 1, j))
+                .expect("nussinov traceback lost its way");
+            deck.push((i, k));
+            deck.push((k + 1, j));
+        }
+    }
+
+    pair_list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rnamanip::{get_dot_bracket, read_sequence};
+
+    /// exhaustive count of the most pairs for short sequences
+    fn most_pairs(seq: &[Nucleotide], i: usize, j: usize) -> u32 {
+        if j < i + MIN_HAIRPIN + 1 {
+            return 0;
+        }
+        let mut best = most_pairs(seq, i + 1, j);
+        for k in i + MIN_HAIRPIN + 1..=j {
+            if can_pair(seq[i], seq[k]) {
+                let rest = if k < j { most_pairs(seq, k + 1, j) } else { 0 };
+                best = best.max(1 + most_pairs(seq, i + 1, k - 1) + rest);
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn traceback_is_nested_and_maximal() {
+        for seq in [
+            "GGGAAAUCC",
+            "GCAUGCAUGCAUGC",
+            "AUGGCUACGUUAGCAU",
+            "GGGAAACCCAGGGAAACCC",
+            "CUUGAGGUAACG",
+        ] {
+            let seq = read_sequence(seq).unwrap();
+            let pair_list = nussinov(&seq);
+            // crossing pairs would get brackets other than ()
+            let sst = get_dot_bracket(&pair_list).unwrap();
+            assert!(sst.chars().all(|c| "().".contains(c)), "{sst}");
+
+            let mut pairs = 0;
+            for (i, pair) in pair_list.iter().enumerate() {
+                if let Some(j) = *pair {
+                    assert_eq!(pair_list[j], Some(i));
+                    assert!(can_pair(seq[i], seq[j]));
+                    assert!(i.abs_diff(j) > MIN_HAIRPIN);
+                    pairs += u32::from(j > i);
+                }
+            }
+            assert_eq!(pairs, most_pairs(&seq, 0, seq.len() - 1));
+        }
+    }
+
+    #[test]
+    fn short_sequences() {
+        assert!(nussinov(&[]).is_empty());
+        let seq = read_sequence("GAAAC").unwrap();
+        assert_eq!(nussinov(&seq), vec![Some(4), None, None, None, Some(0)]);
+        let seq = read_sequence("GAAC").unwrap();
+        assert_eq!(nussinov(&seq), vec![None; 4]);
+    }
+}
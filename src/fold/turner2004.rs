@@ -0,0 +1,144 @@
+//! Nearest neighbour parameters at 37°C from Turner 2004
+//! (Mathews et al. 2004, as distributed with ViennaRNA's rna_turner2004.par).
+//! Energies are in dcal/mol so everything stays in integers.
+//!
+//! Only a subset is shipped: stacking of WC and GU pairs, loop initiations,
+//! terminal AU/GU penalty, interior loop asymmetry and multiloop coefficients.
+//! Terminal mismatches, dangling ends, special hairpins and the 1x1, 1x2, 2x2
+//! interior loop tables are left out; 1x1 and 1x2 loops get generic stand-ins.
+//! Predicted energies are therefore approximate, though good enough
+//! to get a reasonable picture out of a bare sequence.
+use crate::rnamanip::Nucleotide;
+
+/// effectively infinite; small enough to never overflow when summed a few times
+pub const INF: i32 = 10_000_000;
+
+/// loops bigger than this use logarithmic extrapolation
+pub const MAX_LOOP: usize = 30;
+
+/// 1.07856 * 100; RT * ln extrapolation coefficient in dcal/mol
+pub const LXC: f64 = 107.856;
+
+/// Pair types in the order used by [STACK]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PairType {
+    CG = 0,
+    GC = 1,
+    GU = 2,
+    UG = 3,
+    AU = 4,
+    UA = 5,
+}
+
+impl PairType {
+    pub fn new(five: Nucleotide, three: Nucleotide) -> Option<Self> {
+        use Nucleotide::*;
+        let pt = match (five, three) {
+            (C, G) => Self::CG,
+            (G, C) => Self::GC,
+            (G, U) => Self::GU,
+            (U, G) => Self::UG,
+            (A, U) => Self::AU,
+            (U, A) => Self::UA,
+            _ => return None,
+        };
+        Some(pt)
+    }
+
+    /// AU and GU closures pay the terminal penalty
+    pub fn is_weak(self) -> bool {
+        !matches!(self, Self::CG | Self::GC)
+    }
+}
+
+/// STACK[outer][inner] where outer is (i, j) and inner is read
+/// backwards i.e. (l, k) for the enclosed pair (k, l)
+pub const STACK: [[i32; 6]; 6] = [
+    //  CG     GC     GU     UG     AU     UA
+    [-240, -330, -210, -140, -210, -210], // CG
+    [-330, -340, -250, -150, -220, -240], // GC
+    [-210, -250, 130, -50, -140, -130],   // GU
+    [-140, -150, -50, 30, -60, -100],     // UG
+    [-210, -220, -140, -60, -110, -90],   // AU
+    [-210, -240, -130, -100, -90, -130],  // UA
+];
+
+/// hairpin initiation by loop size
+pub const HAIRPIN: [i32; MAX_LOOP + 1] = [
+    INF, INF, INF, 540, 560, 570, 540, 600, 550, 640, 650, 660, 670, 678, 686, 694, 701, 707, 713,
+    719, 725, 730, 735, 740, 744, 749, 753, 757, 761, 765, 769,
+];
+
+/// bulge initiation by loop size
+pub const BULGE: [i32; MAX_LOOP + 1] = [
+    INF, 380, 280, 320, 360, 400, 440, 459, 470, 480, 490, 500, 510, 519, 527, 534, 541, 548, 554,
+    560, 565, 571, 576, 580, 585, 589, 594, 598, 602, 605, 609,
+];
+
+/// interior loop initiation by total loop size;
+/// 2 and 3 stand in for the 1x1 and 1x2 tables
+pub const INTERIOR: [i32; MAX_LOOP + 1] = [
+    INF, INF, 50, 160, 110, 200, 200, 210, 230, 240, 250, 260, 270, 280, 290, 290, 300, 310, 310,
+    320, 330, 330, 340, 340, 350, 350, 350, 360, 360, 370, 370,
+];
+
+/// per nt of interior loop asymmetry
+pub const NINIO: i32 = 60;
+pub const MAX_NINIO: i32 = 300;
+
+/// AU or GU closing an interior loop
+pub const INTERIOR_AU: i32 = 70;
+
+/// AU or GU terminating a helix in exterior loop, multiloop or big bulge
+pub const TERMINAL_AU: i32 = 50;
+
+/// multiloop closing penalty
+pub const ML_CLOSING: i32 = 930;
+/// multiloop per branch (including the closing one)
+pub const ML_INTERN: i32 = -90;
+/// multiloop per unpaired nt
+pub const ML_BASE: i32 = 0;
+
+/// looks up initiation table extrapolating loops bigger than [MAX_LOOP]
+pub fn loop_initiation(table: &[i32; MAX_LOOP + 1], size: usize) -> i32 {
+    if size <= MAX_LOOP {
+        table[size]
+    } else {
+        table[MAX_LOOP] + (LXC * (size as f64 / MAX_LOOP as f64).ln()).round() as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loop_extrapolation() {
+        assert_eq!(loop_initiation(&HAIRPIN, 4), 560);
+        assert_eq!(loop_initiation(&HAIRPIN, MAX_LOOP), 769);
+        // 769 + 107.856 * ln(31 / 30) and ln(2)
+        assert_eq!(loop_initiation(&HAIRPIN, 31), 773);
+        assert_eq!(loop_initiation(&HAIRPIN, 60), 844);
+        assert_eq!(loop_initiation(&BULGE, 60), 684);
+    }
+
+    #[test]
+    fn stacks_read_the_same_from_both_sides() {
+        for (outer, row) in STACK.iter().enumerate() {
+            for (inner, &energy) in row.iter().enumerate() {
+                assert_eq!(STACK[inner][outer], energy);
+            }
+        }
+    }
+
+    #[test]
+    fn pair_types() {
+        use Nucleotide::*;
+        assert_eq!(PairType::new(G, U), Some(PairType::GU));
+        assert_eq!(PairType::new(U, G), Some(PairType::UG));
+        assert_eq!(PairType::new(G, A), None);
+        assert_eq!(PairType::new(X, G), None);
+        assert!(PairType::AU.is_weak() && PairType::UG.is_weak());
+        assert!(!PairType::CG.is_weak());
+    }
+}
@@ -0,0 +1,384 @@
+//! Zuker-style minimum free energy folding
+//! v: pair (i, j) closes a loop; best energy of i..=j given i pairs with j
+//! wm: i..=j is a part of a multiloop with at least one branch
+//! w: exterior loop over 0..j
+use super::turner2004::{self as tp, PairType, INF};
+use super::MIN_HAIRPIN;
+use crate::rnamanip::Nucleotide;
+
+struct Zuker<'a> {
+    seq: &'a [Nucleotide],
+    n: usize,
+    v: Vec<i32>,
+    wm: Vec<i32>,
+    w: Vec<i32>,
+}
+
+/// what the traceback still has to resolve
+enum Trace {
+    V(usize, usize),
+    Wm(usize, usize),
+    W(usize),
+}
+
+impl<'a> Zuker<'a> {
+    fn new(seq: &'a [Nucleotide]) -> Self {
+        let n = seq.len();
+        Self {
+            seq,
+            n,
+            v: vec![INF; n * n],
+            wm: vec![INF; n * n],
+            w: vec![0; n + 1],
+        }
+    }
+
+    fn pair(&self, i: usize, j: usize) -> Option<PairType> {
+        PairType::new(self.seq[i], self.seq[j])
+    }
+
+    fn v(&self, i: usize, j: usize) -> i32 {
+        self.v[i * self.n + j]
+    }
+
+    fn wm(&self, i: usize, j: usize) -> i32 {
+        if i >= j {
+            INF
+        } else {
+            self.wm[i * self.n + j]
+        }
+    }
+
+    /// exterior loop energy of 0..j; w[0] is empty prefix
+    fn w(&self, j: usize) -> i32 {
+        self.w[j]
+    }
+
+    fn terminal(pt: PairType) -> i32 {
+        if pt.is_weak() {
+            tp::TERMINAL_AU
+        } else {
+            0
+        }
+    }
+
+    fn hairpin(&self, i: usize, j: usize, pt: PairType) -> i32 {
+        let size = j - i - 1;
+        let mut energy = tp::loop_initiation(&tp::HAIRPIN, size);
+        if size == MIN_HAIRPIN {
+            energy += Self::terminal(pt);
+        }
+        energy
+    }
+
+    /// stack, bulge or interior loop closed by (i, j) enclosing (k, l)
+    fn interior(
+        &self,
+        i: usize,
+        j: usize,
+        k: usize,
+        l: usize,
+        outer: PairType,
+        inner: PairType,
+    ) -> i32 {
+        let (l1, l2) = (k - i - 1, j - l - 1);
+        // inner pair is read backwards
+        let inner_rev = PairType::new(self.seq[l], self.seq[k]).expect("pair types are symmetric");
+
+        match (l1, l2) {
+            (0, 0) => tp::STACK[outer as usize][inner_rev as usize],
+            (0, size) | (size, 0) => {
+                let mut energy = tp::loop_initiation(&tp::BULGE, size);
+                if size == 1 {
+                    energy += tp::STACK[outer as usize][inner_rev as usize];
+                } else {
+                    energy += Self::terminal(outer) + Self::terminal(inner);
+                }
+                energy
+            }
+            (l1, l2) => {
+                let asymmetry = (l1 as i32 - l2 as i32).abs() * tp::NINIO;
+                let mut energy = tp::loop_initiation(&tp::INTERIOR, l1 + l2);
+                energy += asymmetry.min(tp::MAX_NINIO);
+                for pt in [outer, inner] {
+                    if pt.is_weak() {
+                        energy += tp::INTERIOR_AU;
+                    }
+                }
+                energy
+            }
+        }
+    }
+
+    /// multiloop closed by (i, j): best split of i+1..j-1 into two wm parts
+    fn multiloop(&self, i: usize, j: usize, pt: PairType) -> (i32, usize) {
+        let closing = tp::ML_CLOSING + tp::ML_INTERN + Self::terminal(pt);
+        let mut best = (INF, 0);
+        for u in i + 2..j - 1 {
+            let energy = self.wm(i + 1, u - 1).saturating_add(self.wm(u, j - 1));
+            if energy < best.0 {
+                best = (energy, u);
+            }
+        }
+        (best.0.saturating_add(closing), best.1)
+    }
+
+    fn branch(&self, i: usize, j: usize) -> i32 {
+        match self.pair(i, j) {
+            Some(pt) if self.v(i, j) < INF => self.v(i, j) + tp::ML_INTERN + Self::terminal(pt),
+            _ => INF,
+        }
+    }
+
+    fn fill(&mut self) {
+        let n = self.n;
+        for span in MIN_HAIRPIN + 1..n {
+            for i in 0..n - span {
+                let j = i + span;
+
+                if let Some(pt) = self.pair(i, j) {
+                    let mut best = self.hairpin(i, j, pt);
+                    for k in i + 1..(i + tp::MAX_LOOP + 2).min(j) {
+                        let l1 = k - i - 1;
+                        let lowest_l =
+                            (k + MIN_HAIRPIN + 1).max(j - 1 - (tp::MAX_LOOP - l1).min(j - 1));
+                        for l in (lowest_l..j).rev() {
+                            if self.v(k, l) >= INF {
+                                continue;
+                            }
+                            if let Some(inner) = self.pair(k, l) {
+                                let energy = self.interior(i, j, k, l, pt, inner) + self.v(k, l);
+                                best = best.min(energy);
+                            }
+                        }
+                    }
+                    best = best.min(self.multiloop(i, j, pt).0);
+                    self.v[i * n + j] = best;
+                }
+
+                let mut wm = self.branch(i, j);
+                wm = wm.min(self.wm(i + 1, j).saturating_add(tp::ML_BASE));
+                wm = wm.min(self.wm(i, j - 1).saturating_add(tp::ML_BASE));
+                for u in i + 1..j {
+                    wm = wm.min(self.wm(i, u - 1).saturating_add(self.wm(u, j)));
+                }
+                self.wm[i * n + j] = wm;
+            }
+        }
+
+        for j in 1..=n {
+            let mut best = self.w(j - 1);
+            for i in 0..j {
+                if let Some(pt) = self.pair(i, j - 1) {
+                    let v = self.v(i, j - 1);
+                    if v < INF {
+                        best = best.min(self.w(i) + v + Self::terminal(pt));
+                    }
+                }
+            }
+            self.w[j] = best;
+        }
+    }
+
+    fn traceback(&self) -> Vec<Option<usize>> {
+        let mut pair_list = vec![None; self.n];
+        let mut deck = vec![Trace::W(self.n)];
+
+        while let Some(trace) = deck.pop() {
+            match trace {
+                Trace::W(0) => (),
+                Trace::W(j) => {
+                    if self.w(j) == self.w(j - 1) {
+                        deck.push(Trace::W(j - 1));
+                        continue;
+                    }
+                    let i = (0..j)
+                        .find(|&i| match self.pair(i, j - 1) {
+                            Some(pt) => {
+                                let v = self.v(i, j - 1);
+                                v < INF && self.w(j) == self.w(i) + v + Self::terminal(pt)
+                            }
+                            None => false,
+                        })
+                        .expect("exterior traceback lost its way");
+                    deck.push(Trace::W(i));
+                    deck.push(Trace::V(i, j - 1));
+                }
+                Trace::V(i, j) => {
+                    pair_list[i] = Some(j);
+                    pair_list[j] = Some(i);
+                    let pt = self.pair(i, j).expect("traced pair has to pair");
+                    let target = self.v(i, j);
+                    if target == self.hairpin(i, j, pt) {
+                        continue;
+                    }
+                    if let Some((k, l)) = self.find_interior(i, j, pt, target) {
+                        deck.push(Trace::V(k, l));
+                        continue;
+                    }
+                    let (energy, u) = self.multiloop(i, j, pt);
+                    debug_assert_eq!(energy, target);
+                    deck.push(Trace::Wm(i + 1, u - 1));
+                    deck.push(Trace::Wm(u, j - 1));
+                }
+                Trace::Wm(i, j) => {
+                    let target = self.wm(i, j);
+                    if target == self.branch(i, j) {
+                        deck.push(Trace::V(i, j));
+                    } else if target == self.wm(i + 1, j).saturating_add(tp::ML_BASE) {
+                        deck.push(Trace::Wm(i + 1, j));
+                    } else if target == self.wm(i, j - 1).saturating_add(tp::ML_BASE) {
+                        deck.push(Trace::Wm(i, j - 1));
+                    } else {
+                        let u = (i + 1..j)
+                            .find(|&u| target == self.wm(i, u - 1).saturating_add(self.wm(u, j)))
+                            .expect("multiloop traceback lost its way");
+                        deck.push(Trace::Wm(i, u - 1));
+                        deck.push(Trace::Wm(u, j));
+                    }
+                }
+            }
+        }
+
+        pair_list
+    }
+
+    fn find_interior(
+        &self,
+        i: usize,
+        j: usize,
+        pt: PairType,
+        target: i32,
+    ) -> Option<(usize, usize)> {
+        for k in i + 1..(i + tp::MAX_LOOP + 2).min(j) {
+            let l1 = k - i - 1;
+            let lowest_l = (k + MIN_HAIRPIN + 1).max(j - 1 - (tp::MAX_LOOP - l1).min(j - 1));
+            for l in lowest_l..j {
+                if self.v(k, l) >= INF {
+                    continue;
+                }
+                if let Some(inner) = self.pair(k, l) {
+                    if self.interior(i, j, k, l, pt, inner) + self.v(k, l) == target {
+                        return Some((k, l));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// minimum free energy structure and its energy in kcal/mol; O(n^3)
+pub fn mfe(seq: &[Nucleotide]) -> (Vec<Option<usize>>, f64) {
+    let mut zuker = Zuker::new(seq);
+    zuker.fill();
+    let energy = zuker.w(seq.len()) as f64 / 100.;
+    (zuker.traceback(), energy)
+}
+
+#[cfg(test)]
+mod tests {
+    //! Expected energies are worked out by hand from the parameters
+    //! in [tp]; ViennaRNA adds mismatches and dangles on top
+    //! so its numbers differ slightly for the same structures.
+    use super::*;
+    use crate::rnamanip::{get_dot_bracket, read_sequence};
+
+    fn nts(seq: &str) -> Vec<Nucleotide> {
+        read_sequence(seq).unwrap()
+    }
+
+    /// interior energy of (i, j) enclosing (k, l) in the sequence
+    fn interior(seq: &str, (i, j): (usize, usize), (k, l): (usize, usize)) -> i32 {
+        let seq = nts(seq);
+        let zuker = Zuker::new(&seq);
+        let outer = zuker.pair(i, j).unwrap();
+        let inner = zuker.pair(k, l).unwrap();
+        zuker.interior(i, j, k, l, outer, inner)
+    }
+
+    fn fold(seq: &str) -> (String, f64) {
+        let (pl, energy) = mfe(&read_sequence(seq).unwrap());
+        (get_dot_bracket(&pl).unwrap(), energy)
+    }
+
+    #[test]
+    fn hairpin_energies() {
+        let seq = nts(&format!("G{}C", "A".repeat(38)));
+        let n = seq.len();
+        let zuker = Zuker::new(&seq);
+        let gc = zuker.pair(0, n - 1).unwrap();
+        // loops over 30 nts are extrapolated: 769 + 107.856 * ln(38 / 30)
+        assert_eq!(zuker.hairpin(0, n - 1, gc), 794);
+        assert_eq!(zuker.hairpin(0, 5, gc), tp::HAIRPIN[4]);
+
+        let seq = nts("AAAAU");
+        let zuker = Zuker::new(&seq);
+        let au = zuker.pair(0, 4).unwrap();
+        // triloops closed by AU pay the terminal penalty
+        assert_eq!(zuker.hairpin(0, 4, au), 540 + 50);
+    }
+
+    #[test]
+    fn bulge_energies() {
+        // single nt bulge keeps the stacking of the adjacent pairs: 380 - 330
+        assert_eq!(interior("GAGAAAACC", (0, 8), (2, 7)), 50);
+        // longer ones pay terminal AU penalties instead
+        assert_eq!(interior("GAAGAAAACC", (0, 9), (3, 8)), 280);
+        assert_eq!(interior("AAAGAAAACU", (0, 9), (3, 8)), 280 + 50);
+    }
+
+    #[test]
+    fn interior_loop_energies() {
+        // 1x1 stand-in
+        assert_eq!(interior("GAGAAAACAC", (0, 9), (2, 7)), 50);
+        // 1x3: initiation, asymmetry of 2 and AU closure
+        assert_eq!(interior("AAGAAAACAAAU", (0, 11), (2, 7)), 110 + 120 + 70);
+        // 1x10: asymmetry is capped
+        let seq = format!("GAGAAAAC{}C", "A".repeat(10));
+        assert_eq!(interior(&seq, (0, 18), (2, 7)), 260 + 300);
+    }
+
+    #[test]
+    fn stacks_and_interior_loop_traceback() {
+        // 3 GC/GC stacks and a tetraloop: 3 * -330 + 560
+        assert_eq!(fold("GGGGAAAACCCC"), ("((((....))))".to_owned(), -4.3));
+        // 2 * -330 - 340, 4x4 interior loop 230, -340 - 2 * 330 and a tetraloop 560
+        assert_eq!(
+            fold("GGGCGAAAGCCCAAAAGGGCGAAAGCCC"),
+            ("((((....((((....))))....))))".to_owned(), -12.1)
+        );
+    }
+
+    #[test]
+    fn rnafold_structures() {
+        // MFE structures RNAfold reports for the ViennaRNA tutorial sequence
+        // and for UUCG hairpins; the energies differ as the tetraloop bonus
+        // and mismatches are missing here, the structures must not
+        for (seq, rnafold) in [
+            ("CGCAGGGAUACCCGCG", "(((.(((...))))))"),
+            ("GCGCUUCGGCGC", "((((....))))"),
+            ("GGACUUCGGUCC", "((((....))))"),
+            ("AAAAGGGGAAAACCCCAAAA", "....((((....))))...."),
+        ] {
+            assert_eq!(fold(seq).0, rnafold, "{seq}");
+        }
+    }
+
+    #[test]
+    fn multiloop_traceback() {
+        let seq = "AGAGAGAGGGGAAAACCCCACCCCAAAAGGGGCUCUCUCU";
+        let nts = nts(seq);
+        let mut zuker = Zuker::new(&nts);
+        zuker.fill();
+        // closing AU: 930 - 90 + 50; both branches: -430 - 90
+        let au = zuker.pair(6, 33).unwrap();
+        assert_eq!(zuker.multiloop(6, 33, au).0, 890 - 2 * 520);
+
+        // the above, 6 AU/GC stacks at -210 and -240 and the exterior AU penalty
+        assert_eq!(
+            fold(seq),
+            ("(((((((((((....)))).((((....)))).)))))))".to_owned(), -14.5)
+        );
+    }
+}
@@ -134,7 +134,7 @@ impl<T> Tree<T> {
         self.arena.len()
     }
 
-    pub fn iter(&self) -> ChickenOfTheWoods<T> {
+    pub fn iter(&self) -> ChickenOfTheWoods<'_, T> {
         ChickenOfTheWoods::new(self)
     }
 }
@@ -1,4 +1,7 @@
+pub mod config;
 pub mod draw;
+pub mod error;
+pub mod fold;
 pub mod forest;
 pub mod rnamanip;
 pub mod utils;
@@ -1,13 +1,24 @@
+use std::collections::HashSet;
 use std::ffi::OsStr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{bail, Result};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use plotters::style::RGBColor;
 
-use rnapkin::draw::{self, colors::ColorTheme, Mirror};
+use rnapkin::config::{Config, Defaults};
+use rnapkin::draw::{
+    self, colors, colors::ColorTheme, Colormap, Corner, DataTrack, Extras, LayoutKind, Legend,
+    LineStyle, Mirror, Numbering, Position, Title,
+};
+use rnapkin::error::ParseError;
+use rnapkin::fold::{self, Algorithm, ExternalFolder};
 use rnapkin::forest;
-use rnapkin::rnamanip::{self, Nucleotide};
-use rnapkin::utils::ParsedInput;
+use rnapkin::rnamanip::{self, Nucleotide, SplitPairList};
+use rnapkin::utils::annotation::{self, Annotation};
+use rnapkin::utils::dotplot::{self, PairProbability};
+use rnapkin::utils::{self, shape, ParsedInput, StructureFormat};
 
 const BUBBLE_RADIUS: f64 = 0.5;
 
@@ -18,11 +29,12 @@ struct Args {
     /// file containing secondary_structure and sequence
     input: Option<String>,
 
-    /// Output file; supported extensions: .svg and .png
+    /// Output file; supported extensions: .svg, .png and .pdf;
+    /// pdf embeds the glyphs it uses from the system fonts
     #[arg(short, long)]
     output: Option<String>,
 
-    /// Color theme; dark, bright, white/w, black/b
+    /// Color theme; dark, bright, white/w, black/b or one from the config file
     #[arg(short, long, default_value = "dark")]
     theme: String,
 
@@ -53,46 +65,182 @@ struct Args {
     /// print the svg instead of saving it to a file
     #[arg(short, long, default_value_t = false)]
     svgprint: bool,
+
+    /// Convert structure to dbn, ct or bpseq instead of drawing it;
+    /// written to -o file if given, stdout otherwise
+    #[arg(short, long)]
+    convert: Option<StructureFormat>,
+
+    /// Pseudoknot connector style; solid, dashed, dotted
+    #[arg(long, default_value = "solid")]
+    knot_style: LineStyle,
+
+    /// Pseudoknot connector color as hex e.g. "#8ec07c"; overrides theme
+    #[arg(long)]
+    knot_color: Option<String>,
+
+    /// Directory to draw into; created if missing
+    #[arg(long)]
+    outdir: Option<PathBuf>,
+
+    /// What to do when several records want the same filename
+    #[arg(long, value_enum, default_value_t = OnCollision::Number)]
+    on_collision: OnCollision,
+
+    /// Folding algorithm used when the input has only a sequence; mfe, nussinov.
+    /// mfe knows only part of Turner 2004: stacks, loop initiations, AU/GU penalties,
+    /// interior loop asymmetry and multiloop terms; no terminal mismatches, dangles,
+    /// special hairpins or 1x1, 1x2, 2x2 interior loop tables, so it may disagree with RNAfold
+    #[arg(long, default_value = "mfe")]
+    fold: Algorithm,
+
+    /// External folding program used instead of --fold e.g. "RNAfold --noPS";
+    /// {input} and {output} stand for temporary files if it can't use stdin/stdout
+    #[arg(long)]
+    folder: Option<ExternalFolder>,
+
+    /// What to draw
+    #[arg(short, long, value_enum, default_value_t = Mode::Napkin)]
+    mode: Mode,
+
+    /// Another structure of the same molecule drawn below the arcs in arc mode
+    /// or alongside the others in mountain mode
+    #[arg(long)]
+    compare: Option<String>,
+
+    /// Layout engine; skeleton, naview
+    #[arg(long, default_value = "skeleton")]
+    layout: LayoutKind,
+
+    /// Swing colliding helices apart; helps with big multiloops
+    #[arg(long, default_value_t = false)]
+    declutter: bool,
+
+    /// Draw base pair symbols: line for Watson-Crick, dot for GU, circle for the rest
+    #[arg(long, default_value_t = false)]
+    bonds: bool,
+
+    /// Connect consecutive nucleotides with a line
+    #[arg(long, default_value_t = false)]
+    backbone: bool,
+
+    /// Label every N-th position, 10 if N is not given
+    #[arg(long, num_args = 0..=1, default_missing_value = "10", value_name = "N")]
+    numbering: Option<Numbering>,
+
+    /// Number of the first nucleotide; to match genomic or transcript coordinates
+    #[arg(long, default_value_t = 1, allow_negative_numbers = true)]
+    number_from: i64,
+
+    /// Highlights given as ranges, pairs or structure elements, one per line:
+    /// NAME 12-25,80:93,helix2 [color=#ff0000] [label="text"]
+    #[arg(long)]
+    annotations: Option<String>,
+
+    /// Box in a corner explaining the highlight colors; tl, tr, bl, br (tr if not given)
+    #[arg(long, num_args = 0..=1, default_missing_value = "tr", value_name = "CORNER")]
+    legend: Option<Corner>,
+
+    /// Legend text for a highlight digit, e.g. --legend-label "1=SD sequence";
+    /// annotations are listed by their label or name
+    #[arg(long, value_parser = parse_legend_label, value_name = "DIGIT=TEXT")]
+    legend_label: Vec<(usize, String)>,
+
+    /// Per nucleotide data coloring the bubbles e.g. SHAPE reactivities in .shape or .map format
+    #[arg(long)]
+    data: Option<String>,
+
+    /// Base pair probabilities from a ViennaRNA dot plot (dot.ps, _dp.ps) or an "i j p" list;
+    /// colors nts by the probability of their pair, or of being unpaired
+    #[arg(long, conflicts_with = "data")]
+    probabilities: Option<String>,
+
+    /// Color bonds instead of paired nts by --probabilities; implies --bonds
+    #[arg(long, default_value_t = false, requires = "probabilities")]
+    on_bonds: bool,
+
+    /// Colormap for --data or --probabilities; viridis, reactivity, diverging.
+    /// reactivity for .shape and .map files, viridis otherwise
+    #[arg(long)]
+    colormap: Option<Colormap>,
+
+    /// Values mapped onto the ends of the colormap; data minimum and maximum by default
+    #[arg(long, num_args = 2, value_names = ["MIN", "MAX"], allow_negative_numbers = true)]
+    data_range: Option<Vec<f64>>,
+
+    /// Config file with themes and defaults, TOML or .json; rnapkin/config.toml
+    /// (or config.json) in $XDG_CONFIG_HOME or ~/.config is used if present
+    #[arg(long)]
+    config: Option<String>,
+
+    /// Write the free energy, if known, in the corner of the drawing
+    /// or in the subtitle if there is a --title
+    #[arg(long, default_value_t = false)]
+    energy: bool,
+
+    /// Title over the drawing; the record name if TEXT is not given
+    #[arg(long, num_args = 0..=1, default_missing_value = "", value_name = "TEXT")]
+    title: Option<String>,
+
+    /// Where the structure comes from, e.g. "Rfam RF00162"; goes in the subtitle
+    #[arg(long, requires = "title")]
+    source: Option<String>,
+
+    /// Font family of the title
+    #[arg(long, default_value = "sans-serif", value_name = "FAMILY")]
+    title_font: String,
+
+    /// Title font size in pixels; scales with --height by default
+    #[arg(long, value_name = "PX")]
+    title_size: Option<f64>,
+
+    /// top, bottom, top-left, top-right, bottom-left, bottom-right
+    #[arg(long, default_value = "top", value_name = "POSITION")]
+    title_position: Position,
+}
+
+/// Kind of picture to draw
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// bubbles laid out by --layout
+    Napkin,
+    /// nucleotides on a circle, pairs as chords
+    Circle,
+    /// nucleotides in a row, pairs as arcs; --compare draws another structure below
+    Arc,
+    /// enclosing pairs per position; every record (and --compare) on the same axes
+    Mountain,
+}
+
+/// Policy for records sharing a name in a multi record input
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum OnCollision {
+    /// append _2, _3 ... to the later ones
+    Number,
+    /// later record overwrites the earlier one
+    Overwrite,
+    /// draw only the first one
+    Skip,
 }
 
 fn main() -> Result<()> {
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches)?;
+    let config = Config::load(args.config.as_deref())?;
+    apply_defaults(&mut args, &matches, &config.defaults);
 
-    let pi = match args.input {
-        Some(input) => ParsedInput::from_file(&input)?,
-        None => ParsedInput::from_pipe()?, // carnivorous plant emerges
+    let records = match &args.input {
+        Some(input) => ParsedInput::records_from_file(input)?,
+        None => ParsedInput::records_from_pipe()?, // carnivorous plant emerges
     };
 
-    let mut filename: PathBuf = args
-        .output
-        .unwrap_or_else(|| pi.rna_name.unwrap_or_else(|| "rnaimg.svg".to_owned()))
-        .into();
-
-    if args.svgprint {
-        // note while this is kinda sus
-        // you cannot enter .x via -o or infile >
-        // because it will get changed to o.x.svg
-        filename = PathBuf::from("o.x")
-    } else {
-        match filename.extension().and_then(OsStr::to_str) {
-            Some("png") | Some("svg") => (),
-            _ => {
-                // slapping .svg on top of filename; filename.set_extension() does work
-                // but may overwrite something not meant to be an extension
-                filename = PathBuf::from(format!(
-                    "{}.svg",
-                    filename.to_str().expect("filename is not valid utf8?")
-                ));
-            }
-        };
+    if records.is_empty() {
+        bail!("No structure found in the input! nothing to do :c");
     }
 
-    let mut theme = match args.theme.as_ref() {
-        "dark" => ColorTheme::dark(),
-        "white" | "w" => ColorTheme::white(),
-        "black" | "b" => ColorTheme::black(),
-        "bright" => ColorTheme::bright(),
-        _ => {
+    let mut theme = match config.theme(&args.theme)? {
+        Some(theme) => theme,
+        None => {
             eprintln!(
                 "theme: \"{}\" not recognized!\nfalling back to default",
                 args.theme
@@ -105,31 +253,538 @@ fn main() -> Result<()> {
         theme.bg.3 = bgopacity;
     }
 
-    let (pairlist, sequence) = match (pi.secondary_structure, pi.sequence) {
-        (Some(sst), Some(seq)) => {
-            let pl = rnamanip::get_pair_list(&sst);
-            let seq = rnamanip::read_sequence(&seq);
-            assert_eq!(
-                pl.len(),
-                seq.len(),
-                "sequence and structure have differents lenghts!"
-            );
-            (pl, seq)
+    if let Some(hex) = &args.knot_color {
+        match colors::parse_hex(hex) {
+            Some(color) => theme.knot = color,
+            None => eprintln!("knot color: \"{hex}\" is not a valid hex color; ignoring"),
+        }
+    }
+
+    if let Some(format) = args.convert {
+        let mut converted = String::new();
+        for pi in records {
+            let structure = get_structure(&pi, &args)?;
+            converted += &utils::export(
+                format,
+                pi.rna_name.as_deref(),
+                &structure.sequence,
+                &structure.split.merged(),
+            )?;
+        }
+        match &args.output {
+            Some(path) => {
+                std::fs::write(path, converted)?;
+                println!("{path}");
+            }
+            None => print!("{converted}"),
+        }
+        return Ok(());
+    }
+
+    if let Some(outdir) = &args.outdir {
+        std::fs::create_dir_all(outdir)?;
+    }
+
+    // record n is compared to the n-th record of the other file; or to its only one
+    let compare = match &args.compare {
+        Some(path) => ParsedInput::records_from_file(path)?,
+        None => vec![],
+    };
+
+    if args.mode == Mode::Mountain {
+        return draw_mountains(records, compare, &args, &theme);
+    }
+
+    let data = match (&args.data, &args.probabilities) {
+        (Some(path), _) => Some(load_data(path, &args)?),
+        (None, Some(path)) => Some(DataSource::Probabilities(dotplot::read_pair_probabilities(
+            path,
+        )?)),
+        (None, None) => None,
+    };
+
+    let annotations = match &args.annotations {
+        Some(path) => Annotation::from_file(path)?,
+        None => vec![],
+    };
+
+    let batch = records.len() > 1;
+    let total = records.len();
+    let mut failed = 0;
+    let mut taken: HashSet<PathBuf> = HashSet::new();
+
+    for (n, pi) in records.into_iter().enumerate() {
+        // -o names the output only if there is a single record to draw
+        let name = match (&args.output, &pi.rna_name) {
+            (Some(output), _) if !batch => output.clone(),
+            (_, Some(name)) => utils::safe_file_name(name),
+            (Some(output), None) => output.clone(),
+            (None, None) => "rnaimg.svg".to_owned(),
+        };
+
+        let filename = if args.svgprint {
+            // note while this is kinda sus
+            // you cannot enter .x via -o or infile >
+            // because it will get changed to o.x.svg
+            PathBuf::from("o.x")
+        } else {
+            let filename = with_image_extension(&name);
+            let filename = match &args.outdir {
+                Some(outdir) => outdir.join(filename),
+                None => filename,
+            };
+            match resolve_collision(filename, &mut taken, args.on_collision) {
+                Some(filename) => filename,
+                None => {
+                    eprintln!("skipping \"{name}\": name already taken");
+                    continue;
+                }
+            }
+        };
+
+        if batch && args.points {
+            println!(">{name}");
+        }
+
+        let other = compare.get(n).or(compare.first());
+        let drawn = draw_record(
+            pi,
+            other,
+            data.as_ref(),
+            &annotations,
+            &args,
+            &theme,
+            &filename,
+        );
+        // one broken record shouldn't cost the rest of the batch
+        match drawn {
+            Err(err) if batch => {
+                eprintln!("skipping \"{name}\": {err:#}");
+                failed += 1;
+            }
+            drawn => drawn?,
+        }
+    }
+
+    if failed > 0 {
+        bail!("{failed} of {total} records could not be drawn");
+    }
+    Ok(())
+}
+
+/// what colors the bubbles if not nucleotides
+enum DataSource {
+    Values(DataTrack),
+    /// turned into a track once the structure is known
+    Probabilities(Vec<PairProbability>),
+}
+
+impl DataSource {
+    /// data file shorter than the sequence leaves the rest without data
+    fn track(&self, pair_list: &[Option<usize>], args: &Args) -> Result<DataTrack> {
+        match self {
+            Self::Values(data) => data.clone().fit(pair_list.len()),
+            Self::Probabilities(probabilities) => {
+                let confidence = dotplot::confidence(probabilities, pair_list);
+                let mut data =
+                    DataTrack::new(confidence, args.colormap.unwrap_or(Colormap::Viridis));
+                data.range = match &args.data_range {
+                    Some(range) => (range[0], range[1]),
+                    None => (0., 1.),
+                };
+                Ok(data)
+            }
+        }
+    }
+}
+
+/// reads the data track; the colormap is picked by extension unless given
+fn load_data(path: &str, args: &Args) -> Result<DataSource> {
+    let values = shape::read_reactivity(path)?;
+    let colormap = args.colormap.unwrap_or_else(|| {
+        match Path::new(path).extension().and_then(OsStr::to_str) {
+            Some("shape") | Some("map") => Colormap::Reactivity,
+            _ => Colormap::Viridis,
+        }
+    });
+    let mut data = DataTrack::new(values, colormap);
+    if let Some(range) = &args.data_range {
+        data.range = (range[0], range[1]);
+    }
+    Ok(DataSource::Values(data))
+}
+
+/// DIGIT=TEXT of --legend-label; digits 1-9 pick the theme highlight colors
+fn parse_legend_label(s: &str) -> Result<(usize, String), String> {
+    let (digit, text) = s.split_once('=').unwrap_or((s, ""));
+    match digit.parse::<usize>() {
+        Ok(digit @ 1..=9) => Ok((digit - 1, text.to_owned())),
+        _ => Err(format!(
+            "expected DIGIT=TEXT with a highlight digit 1-9, got {s}"
+        )),
+    }
+}
+
+/// legend entries: labeled highlight digits first, then the annotations
+fn legend(annotations: &[Annotation], args: &Args, theme: &ColorTheme) -> Option<Legend> {
+    let mut legend = Legend {
+        entries: vec![],
+        corner: args.legend?,
+    };
+    for (idx, text) in &args.legend_label {
+        if let Some(&color) = theme.highlights.get(*idx) {
+            legend.push(color, text.clone());
         }
-        (Some(sst), None) => {
-            let pairlist = rnamanip::get_pair_list(&sst);
-            let seq = vec![Nucleotide::X; pairlist.len()]; // TODO del XSequence if am not gonna use it
-            (pairlist, seq)
+    }
+    for (n, annotation) in annotations.iter().enumerate() {
+        let text = annotation.label.as_ref().unwrap_or(&annotation.name);
+        legend.push(annotation.color_or_nth(n, theme), text.clone());
+    }
+    if legend.entries.is_empty() {
+        eprintln!("legend: nothing to explain; try --legend-label or annotations");
+    }
+    Some(legend)
+}
+
+fn delta_g(energy: f64) -> String {
+    format!("ΔG = {energy:.2} kcal/mol")
+}
+
+/// the given text or the record name, with the source and energy below
+fn title(name: Option<&str>, energy: Option<f64>, args: &Args) -> Option<Title> {
+    let text = match (args.title.as_deref()?, name) {
+        ("", Some(name)) => name.to_owned(),
+        ("", None) => {
+            eprintln!("title: record has no name; give one with --title TEXT");
+            return None;
+        }
+        (text, _) => text.to_owned(),
+    };
+    let energy = energy.filter(|_| args.energy).map(delta_g);
+    let subtitle: Vec<String> = args.source.iter().cloned().chain(energy).collect();
+    Some(Title {
+        text,
+        subtitle: (!subtitle.is_empty()).then(|| subtitle.join("  ·  ")),
+        font: args.title_font.clone(),
+        size: args.title_size,
+        position: args.title_position,
+    })
+}
+
+/// config defaults fill in whatever wasn't given on the command line
+fn apply_defaults(args: &mut Args, matches: &ArgMatches, defaults: &Defaults) {
+    let unset = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+    if let (true, Some(theme)) = (unset("theme"), &defaults.theme) {
+        args.theme = theme.clone();
+    }
+    if let (true, Some(height)) = (unset("height"), defaults.height) {
+        args.height = height;
+    }
+    if let (true, Some(angle)) = (unset("angle"), defaults.angle) {
+        args.angle = angle;
+    }
+    if let (true, Some(font)) = (unset("title_font"), &defaults.title_font) {
+        args.title_font = font.clone();
+    }
+    if args.bgopacity.is_none() {
+        args.bgopacity = defaults.bgopacity;
+    }
+    if let (None, Some(folder)) = (&args.folder, &defaults.folder) {
+        match folder.parse() {
+            Ok(folder) => args.folder = Some(folder),
+            Err(err) => eprintln!("config folder: {err}; ignoring"),
+        }
+    }
+}
+
+/// every record goes on one plot so the output is a single file
+fn draw_mountains(
+    records: Vec<ParsedInput>,
+    compare: Vec<ParsedInput>,
+    args: &Args,
+    theme: &ColorTheme,
+) -> Result<()> {
+    let mut tracks = vec![];
+    let mut energies = vec![];
+    for (n, pi) in records.iter().chain(compare.iter()).enumerate() {
+        let structure = get_structure(pi, args)?;
+        let name = pi
+            .rna_name
+            .clone()
+            .unwrap_or_else(|| format!("structure {}", n + 1));
+        if let Some(energy) = structure.energy.filter(|_| args.energy) {
+            energies.push((name.clone(), energy));
         }
-        (None, Some(_)) => unimplemented!(
-            "Calling external soft e.g. RNAFold to get secondary_structure not yet implemented"
+        tracks.push(draw::Track::new(name, &structure.split.merged()));
+    }
+    // a lone structure needs no name in front of its energy
+    let caption = match energies.as_slice() {
+        [] => None,
+        [(_, energy)] if tracks.len() == 1 => Some(delta_g(*energy)),
+        _ => Some(
+            energies
+                .iter()
+                .map(|(name, energy)| format!("{name}: {}", delta_g(*energy)))
+                .collect::<Vec<_>>()
+                .join("  ·  "),
         ),
-        (None, None) => panic!("Neither sequence nor secondary structure found in the input file!"),
     };
 
-    let tree = forest::grow_tree(&pairlist);
+    let filename = if args.svgprint {
+        PathBuf::from("o.x")
+    } else {
+        let name = match (&args.output, &records[0].rna_name) {
+            (Some(output), _) => output.clone(),
+            (None, Some(name)) => format!("{}_mountain", utils::safe_file_name(name)),
+            (None, None) => "mountain.svg".to_owned(),
+        };
+        let filename = with_image_extension(&name);
+        match &args.outdir {
+            Some(outdir) => outdir.join(filename),
+            None => filename,
+        }
+    };
+
+    match draw::plot_mountain(&tracks, &filename, theme, args.height, &caption)? {
+        Some(svg_string) => println!("{svg_string}"),
+        None => println!("{}", &filename.to_str().unwrap()),
+    }
+    Ok(())
+}
+
+/// slaps .svg on top of the filename unless it's already .svg, .png or .pdf
+fn with_image_extension(name: &str) -> PathBuf {
+    let filename = PathBuf::from(name);
+    match filename.extension().and_then(OsStr::to_str) {
+        Some("png") | Some("svg") | Some("pdf") => filename,
+        // filename.set_extension() does work
+        // but may overwrite something not meant to be an extension
+        _ => PathBuf::from(format!("{name}.svg")),
+    }
+}
+
+/// returns filename that should be used according to the policy
+/// or None if the record should be skipped
+fn resolve_collision(
+    filename: PathBuf,
+    taken: &mut HashSet<PathBuf>,
+    policy: OnCollision,
+) -> Option<PathBuf> {
+    if !taken.contains(&filename) {
+        taken.insert(filename.clone());
+        return Some(filename);
+    }
+
+    match policy {
+        OnCollision::Overwrite => Some(filename),
+        OnCollision::Skip => None,
+        OnCollision::Number => {
+            let stem = filename.file_stem().and_then(OsStr::to_str).unwrap_or("");
+            let ext = filename
+                .extension()
+                .and_then(OsStr::to_str)
+                .unwrap_or("svg");
+            let numbered = (2..)
+                .map(|n| filename.with_file_name(format!("{stem}_{n}.{ext}")))
+                .find(|candidate| !taken.contains(candidate))
+                .expect("ran out of numbers");
+            taken.insert(numbered.clone());
+            Some(numbered)
+        }
+    }
+}
+
+/// pair list split into nested part and knots along with the sequence;
+/// sequence is made up of unknown nts if the input lacks it
+struct Structure {
+    split: SplitPairList,
+    sequence: Vec<Nucleotide>,
+    /// kcal/mol; from the input or from folding
+    energy: Option<f64>,
+}
+
+fn get_structure(pi: &ParsedInput, args: &Args) -> Result<Structure> {
+    // only the nested part is laid out; knots are drawn as connectors later
+    let split = match (&pi.pair_list, &pi.secondary_structure) {
+        (Some(pl), _) => Some(rnamanip::split_knots(pl)),
+        (None, Some(sst)) => Some(rnamanip::get_split_pair_list(sst)?),
+        (None, None) => None,
+    };
+
+    let structure = match (split, &pi.sequence) {
+        (Some(split), Some(seq)) => {
+            let seq = rnamanip::read_sequence(seq)?;
+            if split.nested.len() != seq.len() {
+                return Err(ParseError::LengthMismatch {
+                    sequence: seq.len(),
+                    structure: split.nested.len(),
+                }
+                .into());
+            }
+            (split, seq, pi.energy)
+        }
+        (Some(split), None) => {
+            let seq = vec![Nucleotide::X; split.nested.len()]; // TODO del XSequence if am not gonna use it
+            (split, seq, pi.energy)
+        }
+        (None, Some(seq)) => {
+            if let Some(folder) = &args.folder {
+                let folded = ParsedInput {
+                    sequence: Some(seq.clone()),
+                    ..folder.fold(pi.rna_name.as_deref(), seq)?
+                };
+                return get_structure(&folded, args);
+            }
+            let seq = rnamanip::read_sequence(seq)?;
+            let folded = fold::fold(&seq, args.fold);
+            let split = SplitPairList {
+                nested: folded.pair_list,
+                knots: vec![],
+            };
+            (split, seq, folded.energy)
+        }
+        (None, None) => return Err(ParseError::NothingToDraw.into()),
+    };
+
+    let (split, sequence, energy) = structure;
+    Ok(Structure {
+        split,
+        sequence,
+        energy,
+    })
+}
+
+fn draw_record(
+    pi: ParsedInput,
+    compare: Option<&ParsedInput>,
+    data: Option<&DataSource>,
+    annotations: &[Annotation],
+    args: &Args,
+    theme: &ColorTheme,
+    filename: &Path,
+) -> Result<()> {
+    let Structure {
+        split,
+        sequence,
+        energy,
+    } = get_structure(&pi, args)?;
+
+    // TODO highlight is implementation is rushed
+    // I need the functionality but haven't got the time to do it nicely :c
+    let mut highlights: Vec<Option<RGBColor>> = match &pi.highlight {
+        Some(hls) => draw::colors::user_input_to_highlight_indices(hls)
+            .into_iter()
+            .map(|hl| hl.and_then(|idx| theme.highlights.get(idx).copied()))
+            .collect(),
+        None => vec![None; sequence.len()],
+    };
+
+    // ranges from the input and from --annotations paint over the digits
+    let annotations = [pi.annotations.as_slice(), annotations].concat();
+    let regions = annotation::regions(&annotations, &split.merged(), theme)?;
+    if !annotations.is_empty() {
+        let colors = annotation::highlight_colors(&annotations, &split.merged(), theme)?;
+        highlights.resize(sequence.len(), None);
+        for (highlight, color) in highlights.iter_mut().zip(colors) {
+            if color.is_some() {
+                *highlight = color;
+            }
+        }
+    }
+
+    let title = title(pi.rna_name.as_deref(), energy, args);
+    let extras = Extras {
+        highlights,
+        knots: split.knots.clone(),
+        knot_style: args.knot_style,
+        caption: energy
+            .filter(|_| args.energy && title.is_none())
+            .map(delta_g),
+        bonds: match args.bonds || args.on_bonds {
+            true => draw::bonds(&split.nested),
+            false => vec![],
+        },
+        backbone: args.backbone,
+        numbering: args.numbering.map(|numbering| Numbering {
+            offset: args.number_from,
+            ..numbering
+        }),
+        data: data
+            .map(|data| data.track(&split.merged(), args))
+            .transpose()?,
+        data_on_bonds: args.on_bonds,
+        regions,
+        legend: legend(&annotations, args, theme),
+        title,
+    };
+
+    let svgout = match args.mode {
+        Mode::Napkin => {
+            match draw_napkin(&split, &sequence, args, theme, filename, &extras)? {
+                Some(svgout) => svgout,
+                None => return Ok(()), // points were printed instead
+            }
+        }
+        Mode::Circle => draw::plot_circle(
+            &sequence,
+            &split.nested,
+            &filename,
+            theme,
+            args.height,
+            &extras,
+        )?,
+        Mode::Mountain => unreachable!("all records are drawn together"),
+        Mode::Arc => {
+            let other = match compare {
+                Some(other) => Some(get_structure(other, args)?.split.merged()),
+                None => None,
+            };
+            if let Some(other) = &other {
+                if other.len() != sequence.len() {
+                    bail!(
+                        "compared structures differ in length: {} and {}",
+                        sequence.len(),
+                        other.len()
+                    );
+                }
+            }
+            draw::plot_arcs(
+                &sequence,
+                &split.nested,
+                other.as_deref(),
+                &filename,
+                theme,
+                args.height,
+                &extras,
+            )?
+        }
+    };
+
+    match svgout {
+        Some(svg_string) => println!("{svg_string}"),
+        None => println!("{}", &filename.to_str().unwrap()),
+    }
+
+    Ok(())
+}
+
+/// lays out the bubbles and plots them; None if only the points were requested
+fn draw_napkin(
+    split: &SplitPairList,
+    sequence: &[Nucleotide],
+    args: &Args,
+    theme: &ColorTheme,
+    filename: &Path,
+    extras: &Extras,
+) -> Result<Option<Option<String>>> {
+    let tree = forest::grow_tree(&split.nested);
     let mut bubbles =
-        draw::gather_bubbles(&tree, &sequence, BUBBLE_RADIUS, args.angle.to_radians());
+        args.layout
+            .engine(args.angle.to_radians())
+            .layout(&tree, sequence, BUBBLE_RADIUS);
+    if args.declutter {
+        draw::declutter(&mut bubbles, &split.nested, BUBBLE_RADIUS);
+    }
     let mirror = Mirror::new(args.mx, args.my);
 
     if args.points {
@@ -137,30 +792,17 @@ fn main() -> Result<()> {
         for bbl in &bubbles.bubbles {
             println!("{},{},{},{}", bbl.point.x, bbl.point.y, bbl.nt, bbl.pos);
         }
-        return Ok(());
+        return Ok(None);
     }
 
-    // TODO highlight is implementation is rushed
-    // I need the functionality but haven't got the time to do it nicely :c
-    let highlights = match pi.highlight {
-        Some(hls) => draw::colors::user_input_to_highlight_indices(&hls),
-        None => vec![None; sequence.len()],
-    };
-
     let svgout = draw::plot(
         &bubbles,
         BUBBLE_RADIUS,
         &filename,
-        &theme,
+        theme,
         args.height,
         mirror,
-        &highlights,
+        extras,
     )?;
-
-    match svgout {
-        Some(svg_string) => println!("{svg_string}"),
-        None => println!("{}", &filename.to_str().unwrap()),
-    }
-
-    Ok(())
+    Ok(Some(svgout))
 }
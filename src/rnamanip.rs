@@ -1,4 +1,5 @@
 use crate::draw::colors::ColorTheme;
+use crate::error::{Location, ParseError};
 use plotters::style::RGBColor;
 use std::fmt;
 
@@ -8,7 +9,7 @@ const NTC: &str = "C";
 const NTU: &str = "U";
 const NTX: &str = "";
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub enum Nucleotide {
     A,
     U,
@@ -16,6 +17,7 @@ pub enum Nucleotide {
     C,
     // if user provides just secondary structure without seq
     // or uninitiated default nt
+    #[default]
     X,
 }
 
@@ -44,9 +46,29 @@ impl Nucleotide {
     }
 }
 
-impl Default for Nucleotide {
-    fn default() -> Self {
-        Self::X
+/// Leontis–Westhof-ish classification of a base pair;
+/// decides which symbol is drawn between the bubbles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PairKind {
+    /// AU, GC; drawn as a line
+    WatsonCrick,
+    /// GU; drawn as a dot
+    Wobble,
+    /// anything else; drawn as an open circle
+    NonCanonical,
+}
+
+impl PairKind {
+    /// unknown nts are assumed to pair canonically,
+    /// otherwise structure-only input would be full of circles
+    pub fn new(nt0: Nucleotide, nt1: Nucleotide) -> Self {
+        use Nucleotide::*;
+        match (nt0, nt1) {
+            (A, U) | (U, A) | (G, C) | (C, G) => Self::WatsonCrick,
+            (X, _) | (_, X) => Self::WatsonCrick,
+            (G, U) | (U, G) => Self::Wobble,
+            _ => Self::NonCanonical,
+        }
     }
 }
 
@@ -77,30 +99,378 @@ impl std::ops::Index<usize> for XSequence {
     }
 }
 
-pub fn get_pair_list(secondary_structure: &str) -> Vec<Option<usize>> {
-    let mut lovers = vec![None; secondary_structure.len()];
-    let mut deck = vec![];
+/// bracket pairs understood in dot-bracket notation;
+/// on top of these A-Z opens and a-z closes a pair
+const BRACKETS: [(char, char); 4] = [('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')];
+const FAMILIES: usize = BRACKETS.len() + 26;
+
+/// tells which bracket family given symbol belongs to
+/// and whether it opens (true) or closes (false) a pair;
+/// None means the symbol is unpaired
+fn bracket_family(symbol: char) -> Option<(usize, bool)> {
+    for (family, (open, close)) in BRACKETS.iter().enumerate() {
+        if symbol == *open {
+            return Some((family, true));
+        } else if symbol == *close {
+            return Some((family, false));
+        }
+    }
+
+    match symbol {
+        'A'..='Z' => Some((BRACKETS.len() + (symbol as usize - 'A' as usize), true)),
+        'a'..='z' => Some((BRACKETS.len() + (symbol as usize - 'a' as usize), false)),
+        _ => None,
+    }
+}
+
+/// pairs every bracket family on its own; returns (pos, pair) tuples
+/// grouped by family in the order of BRACKETS and then Aa, Bb ...
+fn pairs_by_family(secondary_structure: &str) -> Result<Vec<Vec<(usize, usize)>>, ParseError> {
+    let mut families = vec![vec![]; FAMILIES];
+    let mut decks: Vec<Vec<(usize, char)>> = vec![vec![]; FAMILIES];
 
     for (position, constraint) in secondary_structure.chars().enumerate() {
-        if constraint == '(' {
-            deck.push(position)
-        } else if constraint == ')' {
-            let pair = deck.pop().expect("unpaired bracket");
-            lovers[position] = pair.into();
-            lovers[pair] = position.into();
+        match bracket_family(constraint) {
+            Some((family, true)) => decks[family].push((position, constraint)),
+            Some((family, false)) => {
+                let (pair, _) =
+                    decks[family]
+                        .pop()
+                        .ok_or_else(|| ParseError::UnmatchedBracket {
+                            symbol: constraint,
+                            at: Location::in_string(position),
+                        })?;
+                families[family].push((pair, position));
+            }
+            None => (),
         }
     }
 
-    if !deck.is_empty() {
-        panic!("invalid secondary structure: unclosed bracket")
+    // reports the leftmost one if more than one bracket is left hanging
+    if let Some(&(position, symbol)) = decks.iter().filter_map(|deck| deck.first()).min() {
+        return Err(ParseError::UnclosedBracket {
+            symbol,
+            at: Location::in_string(position),
+        });
     }
 
-    lovers
+    Ok(families)
+}
+
+/// Translates dot-bracket notation into a pair list.
+/// Besides "()" it understands "[]", "{}", "<>" and "Aa", "Bb" ... brackets
+/// so the list may contain crossing pairs (pseudoknots);
+/// see [get_split_pair_list] if you need something [crate::forest::grow_tree] can handle
+pub fn get_pair_list(secondary_structure: &str) -> Result<Vec<Option<usize>>, ParseError> {
+    let mut lovers = vec![None; secondary_structure.chars().count()];
+
+    for (pos, pair) in pairs_by_family(secondary_structure)?.into_iter().flatten() {
+        lovers[pos] = pair.into();
+        lovers[pair] = pos.into();
+    }
+
+    Ok(lovers)
+}
+
+/// Pair list divided into the nested part which
+/// [crate::forest::grow_tree] can handle and the pseudoknotted
+/// pairs crossing it. Knots are kept as (5' pos, 3' pos) tuples
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SplitPairList {
+    pub nested: Vec<Option<usize>>,
+    pub knots: Vec<(usize, usize)>,
+}
+
+impl SplitPairList {
+    /// glues nested part and knots back together into a single pair list
+    pub fn merged(&self) -> Vec<Option<usize>> {
+        let mut pair_list = self.nested.clone();
+        for &(pos, pair) in &self.knots {
+            pair_list[pos] = Some(pair);
+            pair_list[pair] = Some(pos);
+        }
+        pair_list
+    }
 }
 
-pub fn read_sequence(sequence: &str) -> Vec<Nucleotide> {
+/// checks if pair (pos, pair) can join the pair list without crossing anything
+fn fits_nested(pair_list: &[Option<usize>], pos: usize, pair: usize) -> bool {
+    pair_list[pos].is_none()
+        && pair_list[pair].is_none()
+        && pair_list[pos + 1..pair]
+            .iter()
+            .all(|x| x.is_none_or(|x| x > pos && x < pair))
+}
+
+/// Like [get_pair_list] but keeps the nested part separate from the crossing pairs.
+/// Bracket families are consumed in order "()", "[]", "{}", "<>", "Aa", "Bb" ...
+/// and a pair stays in the nested part unless it crosses pairs accepted before it,
+/// so usually "()" is the nested layer and the rest are pseudoknots
+pub fn get_split_pair_list(secondary_structure: &str) -> Result<SplitPairList, ParseError> {
+    let mut nested = vec![None; secondary_structure.chars().count()];
+    let mut knots = vec![];
+
+    for (pos, pair) in pairs_by_family(secondary_structure)?.into_iter().flatten() {
+        if fits_nested(&nested, pos, pair) {
+            nested[pos] = pair.into();
+            nested[pair] = pos.into();
+        } else {
+            knots.push((pos, pair));
+        }
+    }
+
+    knots.sort_unstable();
+    Ok(SplitPairList { nested, knots })
+}
+
+/// walks the pair list with a stack; any pair closing
+/// out of order means there is a pseudoknot somewhere
+fn is_nested(pair_list: &[Option<usize>]) -> bool {
+    let mut deck = vec![];
+    for (pos, pair) in pair_list.iter().enumerate() {
+        match *pair {
+            Some(pair) if pair > pos => deck.push(pos),
+            Some(pair) if deck.pop() != Some(pair) => return false,
+            _ => (),
+        }
+    }
+    true
+}
+
+/// Splits arbitrary (e.g. read from a CT file) pair list into
+/// a maximal nested subset and the remaining crossing pairs.
+/// Dynamic programming runs only over paired positions and keeps
+/// a single row per interval, so memory stays O(paired)
+/// and time O(paired^2) even for a ribosome
+pub fn split_knots(pair_list: &[Option<usize>]) -> SplitPairList {
+    if is_nested(pair_list) {
+        return SplitPairList {
+            nested: pair_list.to_vec(),
+            knots: vec![],
+        };
+    }
+
+    let paired: Vec<usize> = (0..pair_list.len())
+        .filter(|&pos| pair_list[pos].is_some())
+        .collect();
+    let m = paired.len();
+    let mut compressed = vec![0; pair_list.len()];
+    for (k, &pos) in paired.iter().enumerate() {
+        compressed[pos] = k;
+    }
+    let partner: Vec<usize> = paired
+        .iter()
+        .map(|&pos| compressed[pair_list[pos].unwrap()])
+        .collect();
+
+    // value[a]: pair opened at a plus the most nested pairs it can enclose;
+    // shorter pairs go first so whatever they enclose is already known
+    let mut value = vec![0u32; m];
+    let mut openers: Vec<usize> = (0..m).filter(|&a| partner[a] > a).collect();
+    openers.sort_unstable_by_key(|&a| partner[a] - a);
+    for a in openers {
+        value[a] = 1 + *best_within(a + 1, partner[a], &partner, &value)
+            .last()
+            .unwrap();
+    }
+
+    let mut nested = vec![None; pair_list.len()];
+    let mut deck = vec![(0, m)];
+    while let Some((lo, hi)) = deck.pop() {
+        let best = best_within(lo, hi, &partner, &value);
+        let mut b = hi;
+        while b > lo {
+            let k = b - 1;
+            let a = partner[k];
+            if best[b - lo] == best[b - lo - 1] {
+                b -= 1;
+                continue;
+            }
+            nested[paired[a]] = Some(paired[k]);
+            nested[paired[k]] = Some(paired[a]);
+            deck.push((a + 1, k));
+            b = a;
+        }
+    }
+
+    let knots = (0..pair_list.len())
+        .filter_map(|pos| match pair_list[pos] {
+            Some(pair) if pair > pos && nested[pos].is_none() => Some((pos, pair)),
+            _ => None,
+        })
+        .collect();
+
+    SplitPairList { nested, knots }
+}
+
+/// best[i]: most nested pairs within compressed positions lo..lo + i
+fn best_within(lo: usize, hi: usize, partner: &[usize], value: &[u32]) -> Vec<u32> {
+    let mut best = vec![0u32; hi - lo + 1];
+    for k in lo..hi {
+        let a = partner[k];
+        let mut score = best[k - lo];
+        if a < k && a >= lo {
+            score = score.max(best[a - lo] + value[a]);
+        }
+        best[k - lo + 1] = score;
+    }
+    best
+}
+
+/// Translates pair list back into dot-bracket notation.
+/// Maximal nested subset gets "()", maximal nested subset of what's left "[]"
+/// and so on through "{}", "<>", "Aa", "Bb" ... so [get_pair_list]
+/// reproduces the very same pair list
+pub fn get_dot_bracket(pair_list: &[Option<usize>]) -> Result<String, ParseError> {
+    let mut symbols = vec!['.'; pair_list.len()];
+    let mut remaining = pair_list.to_vec();
+
+    for family in 0..FAMILIES {
+        let split = split_knots(&remaining);
+        let (open, close) = match BRACKETS.get(family) {
+            Some(&brackets) => brackets,
+            None => {
+                let offset = (family - BRACKETS.len()) as u8;
+                ((b'A' + offset) as char, (b'a' + offset) as char)
+            }
+        };
+
+        for (pos, pair) in split.nested.iter().enumerate() {
+            match *pair {
+                Some(pair) if pair > pos => symbols[pos] = open,
+                Some(_) => symbols[pos] = close,
+                None => (),
+            }
+        }
+
+        if split.knots.is_empty() {
+            return Ok(symbols.into_iter().collect());
+        }
+
+        remaining = vec![None; pair_list.len()];
+        for (pos, pair) in split.knots {
+            remaining[pos] = Some(pair);
+            remaining[pair] = Some(pos);
+        }
+    }
+
+    Err(ParseError::TooManyKnotLayers)
+}
+
+pub fn read_sequence(sequence: &str) -> Result<Vec<Nucleotide>, ParseError> {
     sequence
         .chars()
-        .map(|c| Nucleotide::from_char(c).expect("invalid nt!"))
+        .enumerate()
+        .map(|(pos, c)| {
+            Nucleotide::from_char(c).ok_or(ParseError::InvalidNucleotide {
+                symbol: c,
+                at: Location::in_string(pos),
+            })
+        })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pair_kinds() {
+        use Nucleotide::*;
+        assert_eq!(PairKind::new(G, C), PairKind::WatsonCrick);
+        assert_eq!(PairKind::new(U, A), PairKind::WatsonCrick);
+        assert_eq!(PairKind::new(U, G), PairKind::Wobble);
+        assert_eq!(PairKind::new(A, G), PairKind::NonCanonical);
+        assert_eq!(PairKind::new(X, G), PairKind::WatsonCrick);
+    }
+
+    #[test]
+    fn pseudoknotted_pair_list() {
+        //          0123456789012345
+        let sst = "((..[[..))..]]..";
+        let pl = get_pair_list(sst).unwrap();
+        assert_eq!(pl[0], Some(9));
+        assert_eq!(pl[1], Some(8));
+        assert_eq!(pl[4], Some(13));
+        assert_eq!(pl[5], Some(12));
+        assert_eq!(pl[2], None);
+
+        let split = get_split_pair_list(sst).unwrap();
+        assert_eq!(split.nested[0], Some(9));
+        assert_eq!(split.nested[4], None);
+        assert_eq!(split.knots, vec![(4, 13), (5, 12)]);
+    }
+
+    #[test]
+    fn every_bracket_family() {
+        let sst = "(<{[A..)>}]a";
+        let split = get_split_pair_list(sst).unwrap();
+        assert_eq!(get_pair_list(sst).unwrap()[4], Some(11));
+        assert_eq!(split.nested[0], Some(7));
+        assert_eq!(split.knots.len(), 4);
+    }
+
+    #[test]
+    fn maximal_nested_subset() {
+        // the longer helix should stay nested
+        let pl = get_pair_list("((..[[[..))..]]]..").unwrap();
+        let split = split_knots(&pl);
+        assert_eq!(split.knots, vec![(0, 10), (1, 9)]);
+        assert_eq!(split.nested, get_pair_list("....(((......)))..").unwrap());
+
+        let nested = get_pair_list("((..))..((..))").unwrap();
+        assert_eq!(split_knots(&nested).nested, nested);
+        assert!(split_knots(&[None, None]).knots.is_empty());
+    }
+
+    #[test]
+    fn large_knot_splits() {
+        // two crossing helices, the longer one stays nested
+        let n = 1000;
+        let sst = format!(
+            "{}{}{}{}",
+            "(".repeat(n),
+            "[".repeat(n + 1),
+            ")".repeat(n),
+            "]".repeat(n + 1)
+        );
+        let split = split_knots(&get_pair_list(&sst).unwrap());
+        assert_eq!(split.knots.len(), n);
+        assert_eq!(split.knots[0], (0, 3 * n));
+        assert_eq!(split.nested[n], Some(4 * n + 1));
+    }
+
+    #[test]
+    fn dot_bracket_round_trip() {
+        for sst in [
+            "..((..))..((..))..",
+            "((..[[..))..]]..",
+            "((..[[..{{..))..]]..}}..",
+            "(<{[A..)>}]a",
+        ] {
+            let pl = get_pair_list(sst).unwrap();
+            assert_eq!(get_pair_list(&get_dot_bracket(&pl).unwrap()).unwrap(), pl);
+        }
+        assert_eq!(
+            get_dot_bracket(&get_pair_list("[[..]]").unwrap()).unwrap(),
+            "((..))"
+        );
+    }
+
+    #[test]
+    fn bracket_errors() {
+        let err = get_pair_list("((..)).))").unwrap_err();
+        assert_eq!(err.to_string(), "unmatched ')' at line 1 col 8");
+        let err = get_pair_list("..[[..(..]]").unwrap_err();
+        assert_eq!(err.to_string(), "unclosed '(' at line 1 col 7");
+        let err = read_sequence("ACGUX").unwrap_err();
+        assert_eq!(err.to_string(), "invalid nucleotide 'X' at line 1 col 5");
+    }
+
+    #[test]
+    fn knot_free_square_brackets_are_nested() {
+        let split = get_split_pair_list("[[..]]..((..))").unwrap();
+        assert!(split.knots.is_empty());
+        assert_eq!(split.nested, get_pair_list("((..))..((..))").unwrap());
+    }
+}
@@ -1,12 +1,26 @@
+pub mod annotation;
+pub mod bpseq;
+pub mod ct;
+pub mod dotplot;
+pub mod shape;
+
+use std::ffi::OsStr;
+use std::fmt::Write;
 use std::fs::File;
 use std::io;
 use std::io::{BufRead, BufReader, Lines};
 use std::path::Path;
+use std::str::FromStr;
 
-use anyhow::{bail, Context, Result};
 use atty::{self, Stream};
 
-fn read_lines<P>(filename: P) -> Result<Lines<BufReader<File>>>
+use crate::error::{Location, ParseError};
+use crate::rnamanip::{self, Nucleotide};
+use annotation::Annotation;
+
+type Result<T> = std::result::Result<T, ParseError>;
+
+fn read_lines<P>(filename: P) -> io::Result<Lines<BufReader<File>>>
 where
     P: AsRef<Path>,
 {
@@ -14,12 +28,100 @@ where
     Ok(BufReader::new(file).lines())
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// collects lines turning io hiccups into ParseError
+fn collect_lines<L>(lines: L, path: &str) -> Result<Vec<String>>
+where
+    L: Iterator<Item = io::Result<String>>,
+{
+    lines
+        .enumerate()
+        .map(|(i, line)| {
+            line.map_err(|source| match source.kind() {
+                io::ErrorKind::InvalidData => ParseError::InvalidUtf8 { line: i + 1 },
+                _ => ParseError::Io {
+                    path: path.to_owned(),
+                    source,
+                },
+            })
+        })
+        .collect()
+}
+
+/// whitespace separated tokens of the line, each with the byte offset it starts at
+pub(crate) fn tokens(line: &str) -> Vec<(usize, &str)> {
+    let mut tokens = vec![];
+    let mut start = None;
+    for (i, c) in line.char_indices() {
+        match (start, c.is_whitespace()) {
+            (Some(s), true) => {
+                tokens.push((s, &line[s..i]));
+                start = None;
+            }
+            (None, false) => start = Some(i),
+            _ => (),
+        }
+    }
+    if let Some(s) = start {
+        tokens.push((s, &line[s..]));
+    }
+    tokens
+}
+
+/// 1-indexed column of the char starting at the byte offset
+pub(crate) fn column_at(line: &str, offset: usize) -> usize {
+    line[..offset].chars().count() + 1
+}
+
+/// byte offset of the first non whitespace char
+pub(crate) fn indent(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct ParsedInput {
     pub sequence: Option<String>,
     pub secondary_structure: Option<String>,
     pub rna_name: Option<String>,
     pub highlight: Option<String>,
+    /// formats like CT carry pairs directly instead of the dot-bracket
+    pub pair_list: Option<Vec<Option<usize>>>,
+    /// free energy in kcal/mol if the input came with one
+    pub energy: Option<f64>,
+    /// highlights given as ranges on lines starting with '@@'
+    pub annotations: Vec<Annotation>,
+}
+
+/// Structure formats rnapkin is able to read and write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StructureFormat {
+    /// rnapkin's flexible dot-bracket + sequence + highlight input
+    DotBracket,
+    /// connectivity table
+    Ct,
+    /// index nucleotide pair-index
+    Bpseq,
+}
+
+impl StructureFormat {
+    pub fn from_extension<P: AsRef<Path>>(path: P) -> Option<Self> {
+        match path.as_ref().extension().and_then(OsStr::to_str) {
+            Some("ct") => Some(Self::Ct),
+            Some("bpseq") => Some(Self::Bpseq),
+            Some("dbn") | Some("db") | Some("fa") | Some("fasta") => Some(Self::DotBracket),
+            _ => None,
+        }
+    }
+
+    /// guesses format by looking at the content; defaults to DotBracket
+    pub fn sniff(lines: &[String]) -> Self {
+        if ct::sniff(lines) {
+            Self::Ct
+        } else if bpseq::sniff(lines) {
+            Self::Bpseq
+        } else {
+            Self::DotBracket
+        }
+    }
 }
 
 fn empty_then_none(s: String) -> Option<String> {
@@ -30,32 +132,208 @@ fn empty_then_none(s: String) -> Option<String> {
     }
 }
 
+/// characters a dot-bracket structure is made of besides pseudoknot letters
+const STRUCTURE: &str = ".()[]{}<>";
+
+/// lines folding programs print next to the structures that aren't data
+const FOLDER_NOTES: [&str; 1] = ["frequency of mfe structure"];
+
+/// sequence never contains brackets or dots
+/// so a line starting with a letter and containing them
+/// has to be a structure beginning with Aa pseudoknot
+fn looks_like_structure(line: &str) -> bool {
+    line.contains(|c| STRUCTURE.contains(c))
+}
+
+impl FromStr for StructureFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "dbn" | "db" | "dot-bracket" => Ok(Self::DotBracket),
+            "ct" => Ok(Self::Ct),
+            "bpseq" => Ok(Self::Bpseq),
+            _ => Err(format!(
+                "unknown structure format: {s}; try dbn, ct or bpseq"
+            )),
+        }
+    }
+}
+
+/// Turns a record name into something safe to use as a file name:
+/// anything but ascii letters, digits, '-', '_' and '.' becomes '_'
+/// and leading dots are replaced too, so "../x" can't escape the directory
+pub fn safe_file_name(name: &str) -> String {
+    let safe: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect();
+    let dots = safe.len() - safe.trim_start_matches('.').len();
+    match "_".repeat(dots) + &safe[dots..] {
+        safe if safe.is_empty() => "rnaimg".to_owned(),
+        safe => safe,
+    }
+}
+
+/// Writes structure in the requested format;
+/// for dot-bracket sequence is omitted if it's made only of unknown nts
+pub fn export(
+    format: StructureFormat,
+    name: Option<&str>,
+    sequence: &[Nucleotide],
+    pair_list: &[Option<usize>],
+) -> Result<String> {
+    match format {
+        StructureFormat::Ct => Ok(ct::write(name, sequence, pair_list)),
+        StructureFormat::Bpseq => Ok(bpseq::write(name, sequence, pair_list)),
+        StructureFormat::DotBracket => {
+            let mut out = String::with_capacity(2 * pair_list.len() + 64);
+            if let Some(name) = name {
+                writeln!(out, ">{name}").unwrap();
+            }
+            if sequence.iter().any(|nt| !matches!(nt, Nucleotide::X)) {
+                let seq: String = sequence.iter().map(|nt| nt.to_string()).collect();
+                writeln!(out, "{seq}").unwrap();
+            }
+            writeln!(out, "{}", rnamanip::get_dot_bracket(pair_list)?).unwrap();
+            Ok(out)
+        }
+    }
+}
+
+fn first_record(records: Vec<ParsedInput>) -> Result<ParsedInput> {
+    match records.into_iter().next() {
+        Some(record) => Ok(record),
+        None => Err(ParseError::NothingToDraw),
+    }
+}
+
+/// remembers where a chunk of concatenated sequence or structure came from
+/// so positions within it can be traced back to the input
+#[derive(Debug, Default)]
+struct Origins {
+    /// (offset within the concatenated string, line, col) of every chunk
+    chunks: Vec<(usize, usize, usize)>,
+    len: usize,
+}
+
+impl Origins {
+    fn push(&mut self, line_no: usize, col: usize, chunk: &str) {
+        self.chunks.push((self.len, line_no, col));
+        self.len += chunk.chars().count();
+    }
+
+    fn locate(&self, pos: usize) -> Location {
+        let idx = self.chunks.partition_point(|&(offset, _, _)| offset <= pos);
+        let (offset, line, col) = self.chunks[idx.saturating_sub(1)];
+        Location::new(line, col + pos - offset)
+    }
+}
+
 impl ParsedInput {
-    /// reads file and passes it to Self::parse
+    /// reads file and returns its first record
     pub fn from_file(input_file: &str) -> Result<Self> {
-        let mut lines = read_lines(input_file)
-            .with_context(|| format!("could not read file: {input_file}"))?
-            .map(|x| x.expect("invalid utf8?"));
-        Self::parse(&mut lines)
+        first_record(Self::records_from_file(input_file)?)
     }
 
-    /// reads stdin and passes it to Self::parse
+    /// reads stdin and returns its first record
     pub fn from_pipe() -> Result<Self> {
+        first_record(Self::records_from_pipe()?)
+    }
+
+    /// reads file and parses every record in it;
+    /// format is picked by extension or by sniffing the content
+    pub fn records_from_file(input_file: &str) -> Result<Vec<Self>> {
+        let lines = read_lines(input_file).map_err(|source| ParseError::Io {
+            path: input_file.to_owned(),
+            source,
+        })?;
+        let lines = collect_lines(lines, input_file)?;
+        let format = StructureFormat::from_extension(input_file)
+            .unwrap_or_else(|| StructureFormat::sniff(&lines));
+        Self::parse_records(&lines, format)
+    }
+
+    /// reads stdin and parses every record in it; format is sniffed
+    pub fn records_from_pipe() -> Result<Vec<Self>> {
         if atty::is(Stream::Stdin) {
-            bail!("No input provided! nothing to do :c")
+            return Err(ParseError::NoInput);
         }
 
         let stdin = io::stdin();
-        let mut lines = stdin.lock().lines().map(|x| x.expect("invalid utf8?"));
-        Self::parse(&mut lines)
+        let lines = collect_lines(stdin.lock().lines(), "stdin")?;
+        let format = StructureFormat::sniff(&lines);
+        Self::parse_records(&lines, format)
+    }
+
+    /// dispatches lines to the parser of given format
+    pub fn parse_records(lines: &[String], format: StructureFormat) -> Result<Vec<Self>> {
+        match format {
+            StructureFormat::DotBracket => Self::parse_fasta(lines),
+            StructureFormat::Ct => ct::parse(lines),
+            StructureFormat::Bpseq => Ok(vec![bpseq::parse(lines)?]),
+        }
+    }
+
+    /// splits multi-FASTA / multi-dbn input on ">" headers
+    /// and parses every chunk on its own; lines before the first
+    /// header form a nameless record. Records without any data are dropped
+    pub fn parse_fasta(lines: &[String]) -> Result<Vec<Self>> {
+        let mut chunks: Vec<(usize, &[String])> = vec![];
+        let mut start = 0;
+        for (i, line) in lines.iter().enumerate() {
+            if line.trim_start().starts_with('>') && i > start {
+                chunks.push((start, &lines[start..i]));
+                start = i;
+            }
+        }
+        chunks.push((start, &lines[start..]));
+
+        let mut records = vec![];
+        for (start, chunk) in chunks {
+            for record in Self::parse_from(&mut chunk.iter().cloned(), start + 1)? {
+                if record.sequence.is_some() || record.secondary_structure.is_some() {
+                    records.push(record);
+                }
+            }
+        }
+        Ok(records)
     }
 
     /// parses user provided input reads lines and checks the first byte:
     /// interprets A-Ua-u as nucleotides
-    /// interprets .() as secondary structure
+    /// unless the line contains brackets or dots; then it's a pseudoknot letter
+    /// interprets .()[]{}<> as secondary structure
+    /// interprets 0-9 as highlight
     /// interprets > as name
-    /// ignores everything else
+    /// ignores lines starting with anything else (e.g. "#") as comments
+    ///
+    /// Folders like RNAfold and RNAsubopt follow the first column with an energy
+    /// e.g. "((..)) (-3.40)"; it is split off and stored in [Self::energy].
+    /// Ensemble lines printed with -p ("{,,..} [-3.60]", centroid, MEA) are skipped
+    /// and so is RNAfold's "frequency of mfe structure..." note.
+    /// Data split into groups ("GGGG AAAA CCCC") is joined back, while any other
+    /// text trailing it, a broken energy or a line that starts like data
+    /// but isn't any is an error.
+    ///
+    /// sequence and structure are validated on the way out so the errors
+    /// can point to the offending line and column.
+    /// Only the first structure is returned, see [Self::parse_fasta] for the rest
     pub fn parse<L>(lines: &mut L) -> Result<Self>
+    where
+        L: Iterator<Item = String>,
+    {
+        let records = Self::parse_from(lines, 1)?;
+        Ok(records.into_iter().next().unwrap_or_default())
+    }
+
+    /// [Self::parse] with line numbering starting at first_line;
+    /// every structure that ends with an energy (RNAsubopt style)
+    /// becomes its own record sharing name, sequence and highlight
+    fn parse_from<L>(lines: &mut L, first_line: usize) -> Result<Vec<Self>>
     where
         L: Iterator<Item = String>,
     {
@@ -63,32 +341,290 @@ impl ParsedInput {
         let mut secondary_structure = String::with_capacity(300);
         let mut highlight = String::with_capacity(300);
         let mut rna_name: Option<String> = None;
+        let mut annotations = vec![];
+        let mut sequence_origins = Origins::default();
+        let mut structure_origins = Origins::default();
+        // finished structures along with their energies
+        let mut structures: Vec<(String, Origins, Option<f64>)> = vec![];
 
-        for line in lines {
+        for (line_no, line) in lines.enumerate() {
+            let line_no = line_no + first_line;
             let trimmed = line.trim();
             if trimmed.is_empty() {
                 continue;
             }
-            match &trimmed[0..1].as_bytes()[0] {
-                0x41..=0x55 | 0x61..=0x75 => sequence.push_str(trimmed), // [A-Ua-u] can catch some non nt but then the input is doomed anyway
-                0x2e | 0x28 | 0x29 => secondary_structure.push_str(trimmed), // .()
-                0x30..=0x39 => highlight.push_str(trimmed),              // 0-9
-                0x3e => rna_name = Some(line[1..].trim().replace(' ', "_")), // >
+            if let Some(name) = trimmed.strip_prefix('>') {
+                rna_name = Some(name.trim().replace(' ', "_"));
+                continue;
+            }
+            if trimmed.starts_with(annotation::MARKER) {
+                let start = indent(&line) + annotation::MARKER.len();
+                annotations.push(Annotation::parse(&line, start, line_no)?);
+                continue;
+            }
+
+            if !trimmed.starts_with(|c: char| c.is_ascii_alphanumeric() || STRUCTURE.contains(c))
+                || FOLDER_NOTES.iter().any(|note| trimmed.starts_with(note))
+            {
+                continue;
+            }
+
+            let words = tokens(&line);
+            let (head_at, head) = words[0];
+            let rest = &line[head_at + head.len()..];
+            let rest_at = line.len() - rest.trim_start().len();
+            let unexpected = |offset: usize, text: &str, kind: &str| {
+                ParseError::malformed(
+                    "dbn",
+                    Location::new(line_no, column_at(&line, offset)),
+                    format!("unexpected \"{text}\" after the {kind}"),
+                )
+            };
+            let kind = DataKind::of(head);
+            let mut trailer = match kind {
+                // numbers after a highlight are more highlight, not an energy
+                Some(DataKind::Highlight) => Trailer::Prose,
+                _ => Trailer::new(rest),
+            };
+
+            let mut groups = vec![words[0]];
+            match (trailer, kind) {
+                (Trailer::Prose, None) => {
+                    return Err(ParseError::malformed(
+                        "dbn",
+                        Location::new(line_no, column_at(&line, head_at)),
+                        format!("\"{head}\" is not a sequence, structure or highlight"),
+                    ))
+                }
+                (Trailer::Prose, Some(kind)) => {
+                    match words[1..]
+                        .iter()
+                        .find(|(_, t)| DataKind::of(t) != Some(kind))
+                    {
+                        Some(&(offset, text)) => return Err(unexpected(offset, text, kind.name())),
+                        None => groups = words,
+                    }
+                    trailer = Trailer::Nothing;
+                }
+                (Trailer::Malformed, _) => {
+                    return Err(ParseError::malformed(
+                        "dbn",
+                        Location::new(line_no, column_at(&line, rest_at)),
+                        format!("\"{}\" is not an energy", rest.trim()),
+                    ));
+                }
+                _ => (),
+            }
+
+            match &head.as_bytes()[0] {
+                // [A-Ua-u] can catch some non nt but then the input is doomed anyway
+                0x41..=0x55 | 0x61..=0x75 if !looks_like_structure(head) => {
+                    if trailer == Trailer::Ensemble {
+                        return Err(unexpected(rest_at, rest.trim(), "sequence"));
+                    }
+                    for (offset, group) in groups {
+                        sequence_origins.push(line_no, column_at(&line, offset), group);
+                        sequence.push_str(group)
+                    }
+                }
+                // Aa pseudoknots and .()[]{}<
+                0x41..=0x5a
+                | 0x61..=0x7a
+                | 0x2e
+                | 0x28
+                | 0x29
+                | 0x5b
+                | 0x5d
+                | 0x7b
+                | 0x7d
+                | 0x3c => match trailer {
+                    Trailer::Ensemble => continue,
+                    Trailer::Energy(energy) => {
+                        structure_origins.push(line_no, column_at(&line, head_at), head);
+                        secondary_structure.push_str(head);
+                        structures.push((
+                            std::mem::take(&mut secondary_structure),
+                            std::mem::take(&mut structure_origins),
+                            Some(energy),
+                        ));
+                    }
+                    _ => {
+                        for (offset, group) in groups {
+                            structure_origins.push(line_no, column_at(&line, offset), group);
+                            secondary_structure.push_str(group)
+                        }
+                    }
+                },
+                // 0-9
+                0x30..=0x39 => match trailer {
+                    Trailer::Nothing => groups.iter().for_each(|(_, g)| highlight.push_str(g)),
+                    _ => {
+                        return Err(unexpected(rest_at, rest.trim(), "highlight"));
+                    }
+                },
                 _ => continue,
             }
         }
 
-        Ok(ParsedInput {
+        if !secondary_structure.is_empty() {
+            structures.push((secondary_structure, structure_origins, None));
+        }
+
+        if !sequence.is_empty() {
+            rnamanip::read_sequence(&sequence)
+                .map_err(|e| e.relocate(|pos| sequence_origins.locate(pos)))?;
+        }
+
+        for (secondary_structure, structure_origins, _) in &structures {
+            rnamanip::get_pair_list(secondary_structure)
+                .map_err(|e| e.relocate(|pos| structure_origins.locate(pos)))?;
+
+            if !sequence.is_empty() && sequence_origins.len != structure_origins.len {
+                return Err(ParseError::LengthMismatch {
+                    sequence: sequence_origins.len,
+                    structure: structure_origins.len,
+                });
+            }
+        }
+
+        let record = ParsedInput {
             sequence: empty_then_none(sequence),
-            secondary_structure: empty_then_none(secondary_structure),
             highlight: empty_then_none(highlight),
             rna_name,
-        })
+            annotations,
+            ..ParsedInput::default()
+        };
+
+        if structures.is_empty() {
+            return Ok(vec![record]);
+        }
+
+        Ok(structures
+            .into_iter()
+            .map(|(secondary_structure, _, energy)| ParsedInput {
+                secondary_structure: Some(secondary_structure),
+                energy,
+                ..record.clone()
+            })
+            .collect())
+    }
+}
+
+/// what a whitespace separated group of characters could be a part of
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DataKind {
+    Sequence,
+    Structure,
+    Highlight,
+}
+
+impl DataKind {
+    fn of(group: &str) -> Option<Self> {
+        let structure = |c: char| c.is_ascii_alphabetic() || STRUCTURE.contains(c);
+        if looks_like_structure(group) && group.chars().all(structure) {
+            Some(Self::Structure)
+        } else if group.chars().all(|c| Nucleotide::from_char(c).is_some()) {
+            Some(Self::Sequence)
+        } else if group.chars().all(|c| c.is_ascii_digit()) {
+            Some(Self::Highlight)
+        } else {
+            None
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Sequence => "sequence",
+            Self::Structure => "structure",
+            Self::Highlight => "highlight",
+        }
+    }
+}
+
+/// whatever follows the first column of a line
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Trailer {
+    Nothing,
+    /// "(-3.40)", "( -3.40)" from RNAfold or bare numbers from RNAsubopt;
+    /// for sequence lines the numbers are RNAsubopt's energy range and are ignored
+    Energy(f64),
+    /// "[-3.60]" ensemble free energy and "{-3.40 d=2.1}" centroid or MEA
+    Ensemble,
+    /// bracketed number that doesn't parse e.g. "( -3.4x)"
+    Malformed,
+    /// anything else; fine only if it's more of the same data
+    Prose,
+}
+
+impl Trailer {
+    fn new(rest: &str) -> Self {
+        let rest = rest.trim();
+        let Some(first) = rest.chars().next() else {
+            return Self::Nothing;
+        };
+
+        let number = |inner: &str| {
+            inner
+                .split_whitespace()
+                .next()
+                .and_then(|t| t.parse::<f64>().ok())
+        };
+
+        // structure chars never include digits; a failed number in brackets is a broken energy
+        let broken = match rest.contains(|c: char| c.is_ascii_digit()) {
+            true => Self::Malformed,
+            false => Self::Prose,
+        };
+
+        match first {
+            '(' | '[' | '{' => {
+                let close = match first {
+                    '(' => ')',
+                    '[' => ']',
+                    _ => '}',
+                };
+                match rest.strip_suffix(close).and_then(|r| number(&r[1..])) {
+                    Some(energy) if first == '(' => Self::Energy(energy),
+                    Some(_) => Self::Ensemble,
+                    None => broken,
+                }
+            }
+            _ => {
+                let mut numbers = rest.split_whitespace().map(|t| t.parse::<f64>());
+                match numbers.next() {
+                    Some(Ok(energy)) if numbers.all(|n| n.is_ok()) => Self::Energy(energy),
+                    _ => Self::Prose,
+                }
+            }
+        }
+    }
+}
+
+/// helpers shared by the tests of the structure file readers
+#[cfg(test)]
+pub(crate) mod testing {
+    use crate::error::ParseError;
+
+    pub(crate) fn lines(s: &str) -> Vec<String> {
+        s.lines().map(|l| l.to_string()).collect()
+    }
+
+    /// error message for the text with the first `from` replaced by `to`
+    pub(crate) fn error_with<T: std::fmt::Debug>(
+        text: &str,
+        from: &str,
+        to: &str,
+        parse: impl Fn(&[String]) -> Result<T, ParseError>,
+    ) -> String {
+        parse(&lines(&text.replacen(from, to, 1)))
+            .unwrap_err()
+            .to_string()
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::testing::lines;
     use super::*;
     const TENA: &str = r#">TPP_riboswitch
         GCAGAACAATTCAATATGTATTCGTTTAACCACTAGGGGTGTCCTTCATAAGGGCTGAGA
@@ -128,6 +664,11 @@ mod tests {
         ParsedInput::parse(&mut lineiter).expect("failed parsing input")
     }
 
+    fn parse_helper_err(test_rna: &str) -> ParseError {
+        let mut lineiter = test_rna.split("\n").map(|x| x.to_string());
+        ParsedInput::parse(&mut lineiter).expect_err("input should be invalid")
+    }
+
     #[test]
     fn parse_simple_input() {
         let seq =
@@ -141,6 +682,9 @@ mod tests {
             secondary_structure: Some(sst.to_string()),
             rna_name: None,
             highlight: None,
+            pair_list: None,
+            energy: None,
+            annotations: vec![],
         };
 
         let test_rna = format!("{}\n{}\n", seq, sst);
@@ -168,6 +712,211 @@ mod tests {
         assert_eq!(named_correct_pi, pi);
     }
 
+    #[test]
+    fn parse_pseudoknotted_input() {
+        let seq = "GGCGAAAGCCAAAGGCUUUAA";
+        let sst = "[[[...(((]]]...)))...";
+        let sst_aa = "AAA...(((aaa...)))...";
+        let pi = parse_helper(&format!("{}\n{}\n", seq, sst));
+        assert_eq!(pi.sequence.as_deref(), Some(seq));
+        assert_eq!(pi.secondary_structure.as_deref(), Some(sst));
+
+        let pi = parse_helper(&format!("{}\n{}\n", sst_aa, seq));
+        assert_eq!(pi.sequence.as_deref(), Some(seq));
+        assert_eq!(pi.secondary_structure.as_deref(), Some(sst_aa));
+    }
+
+    #[test]
+    fn export_dot_bracket_round_trip() {
+        let sst = "((..[[..))..]]..";
+        let seq = rnamanip::read_sequence("GGAAGGAACCAACCAA").unwrap();
+        let pl = rnamanip::get_pair_list(sst).unwrap();
+        let out = export(StructureFormat::DotBracket, Some("knotty"), &seq, &pl).unwrap();
+        let pi = parse_helper(&out);
+        assert_eq!(pi.rna_name.as_deref(), Some("knotty"));
+        assert_eq!(pi.sequence.as_deref(), Some("GGAAGGAACCAACCAA"));
+        assert_eq!(
+            rnamanip::get_pair_list(&pi.secondary_structure.unwrap()).unwrap(),
+            pl
+        );
+
+        for format in [StructureFormat::Ct, StructureFormat::Bpseq] {
+            let out = export(format, Some("knotty"), &seq, &pl).unwrap();
+            let lines = lines(&out);
+            assert_eq!(StructureFormat::sniff(&lines), format);
+            let pi = &ParsedInput::parse_records(&lines, format).unwrap()[0];
+            assert_eq!(pi.pair_list.as_ref(), Some(&pl));
+        }
+    }
+
+    #[test]
+    fn export_dot_bracket_without_sequence() {
+        let seq = rnamanip::read_sequence("NNNNNNN").unwrap();
+        let pl = rnamanip::get_pair_list("((...))").unwrap();
+        let out = export(StructureFormat::DotBracket, None, &seq, &pl).unwrap();
+        assert_eq!(out, "((...))\n");
+    }
+
+    #[test]
+    fn parse_multi_record() {
+        let input =
+            format!("{TENA}\n>hairpin\nGGGAAACCC\n(((...)))\n\n>only structure\n..((...))..");
+        let lines = lines(&input);
+        let records = ParsedInput::parse_fasta(&lines).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].sequence.as_deref(), Some(TENASEQ));
+        assert_eq!(records[0].rna_name.as_deref(), Some(TENANAME));
+        assert_eq!(records[1].sequence.as_deref(), Some("GGGAAACCC"));
+        assert_eq!(records[1].rna_name.as_deref(), Some("hairpin"));
+        assert_eq!(records[2].sequence, None);
+        assert_eq!(records[2].rna_name.as_deref(), Some("only_structure"));
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_input() {
+        let broken = ">broken\nGGGAAACCC\n  (((...\n  ))).)\n";
+        let err = parse_helper_err(broken);
+        assert_eq!(err.to_string(), "unmatched ')' at line 4 col 7");
+
+        let broken = ">broken\nGGGAAACCC\nGGGAAJCCC\n";
+        let err = parse_helper_err(broken);
+        assert_eq!(err.to_string(), "invalid nucleotide 'J' at line 3 col 6");
+
+        let lines: Vec<String> = format!("{TENA}\n>second\n((...)\n")
+            .lines()
+            .map(|l| l.to_string())
+            .collect();
+        let err = ParsedInput::parse_fasta(&lines).unwrap_err();
+        assert_eq!(err.to_string(), "unclosed '(' at line 12 col 1");
+
+        let err = parse_helper_err("GGGAAACC\n(((...)))");
+        assert!(matches!(
+            err,
+            ParseError::LengthMismatch {
+                sequence: 8,
+                structure: 9
+            }
+        ));
+    }
+
+    #[test]
+    fn parse_folder_output() {
+        let rnafold = ">hairpin\nGGGGAAAACCCC\n((((....)))) ( -4.30)\n\
+            ((((....)))) [-4.52]\n{(((....)))} [-4.52]\n((((....)))) {-4.30 d=0.54}\n\
+            {,{{....}},} [-4.52]\n frequency of mfe structure in ensemble 0.7; ensemble diversity 0.9";
+        let pi = parse_helper(rnafold);
+        assert_eq!(pi.secondary_structure.as_deref(), Some("((((....))))"));
+        assert_eq!(pi.sequence.as_deref(), Some("GGGGAAAACCCC"));
+        assert_eq!(pi.energy, Some(-4.3));
+
+        let subopt = ">hairpin\nGGGGAAAACCCC  -430    200\n((((....))))  -4.30\n\
+            .(((....))).  -2.60\n";
+        let lines = lines(subopt);
+        let records = ParsedInput::parse_fasta(&lines).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[1].secondary_structure.as_deref(),
+            Some(".(((....))).")
+        );
+        assert_eq!(records[1].energy, Some(-2.6));
+        assert_eq!(records[1].rna_name.as_deref(), Some("hairpin"));
+        assert_eq!(records[1].sequence, records[0].sequence);
+    }
+
+    #[test]
+    fn parse_subopt_rows() {
+        // RNAsubopt -e 1 -s; the header holds mfe and range in dcal/mol
+        let subopt = "\
+>tutorial
+CGCAGGGAUACCCGCG  -560   100
+(((.(((...)))))) -5.60
+((((((...)).)))) -4.90
+(((..((...)).))) -4.70
+.((.(((...))))). -4.60
+";
+        let records = ParsedInput::parse_fasta(&lines(subopt)).unwrap();
+        let energies: Vec<_> = records.iter().map(|r| r.energy.unwrap()).collect();
+        assert_eq!(energies, [-5.6, -4.9, -4.7, -4.6]);
+        assert!(records
+            .iter()
+            .all(|r| r.sequence.as_deref() == Some("CGCAGGGAUACCCGCG")
+                && r.rna_name.as_deref() == Some("tutorial")));
+        assert_eq!(
+            records[3].secondary_structure.as_deref(),
+            Some(".((.(((...))))).")
+        );
+    }
+
+    #[test]
+    fn reject_lines_that_are_not_data() {
+        let err = parse_helper_err(">t\nGGGGAAAACCCC\n((((....)))) ( -4.3x)\n");
+        assert_eq!(
+            err.to_string(),
+            "dbn: \"( -4.3x)\" is not an energy at line 3 col 14"
+        );
+        let err = parse_helper_err(">t\nGGGGAAAACCCC\n((((....)))) [-4.52\n");
+        assert_eq!(err.location(), Some(Location::new(3, 14)));
+
+        let err = parse_helper_err(">t\nGGGGAAAACCCC\n{,{{....}},} ensemble\n");
+        assert_eq!(
+            err.to_string(),
+            "dbn: \"{,{{....}},}\" is not a sequence, structure or highlight at line 3 col 1"
+        );
+        let err = parse_helper_err(">t\nGGGGAAAACCCC\n000011110000 note\n");
+        assert_eq!(
+            err.to_string(),
+            "dbn: unexpected \"note\" after the highlight at line 3 col 14"
+        );
+
+        // comments and grouped highlight are fine
+        let pi = parse_helper("# note\n* note\nGGGGAAAACCCC\n0000 1111 0000\n");
+        assert_eq!(pi.highlight.as_deref(), Some("000011110000"));
+    }
+
+    #[test]
+    fn parse_grouped_and_trailing_text() {
+        let grouped = ">t\nGGGG AAAA CCCC\n(((( .... ))))\n";
+        let pi = parse_helper(grouped);
+        assert_eq!(pi.sequence.as_deref(), Some("GGGGAAAACCCC"));
+        assert_eq!(pi.secondary_structure.as_deref(), Some("((((....))))"));
+
+        let err = parse_helper_err(">t\nGGGGAAAACCCC\n.(((....))). my note\n");
+        assert_eq!(
+            err.to_string(),
+            "dbn: unexpected \"my\" after the structure at line 3 col 14"
+        );
+        let err = parse_helper_err(">t\nGGGG AAAA CCCC (from Rfam)\n");
+        assert_eq!(err.location(), Some(Location::new(2, 16)));
+    }
+
+    #[test]
+    fn parse_inline_annotations() {
+        let input = [">ann", "GGGAAACCC", "(((...)))", "@@ loop hairpin1 color=2"];
+        let records = ParsedInput::parse_from(&mut input.iter().map(|l| l.to_string()), 1).unwrap();
+        assert_eq!(records[0].annotations.len(), 1);
+        assert_eq!(records[0].annotations[0].name, "loop");
+        assert_eq!(records[0].annotations[0].at, Location::new(4, 4));
+
+        let bad = [">ann", "GGGAAACCC", "@@ loop 4-2"];
+        let err = ParsedInput::parse_from(&mut bad.iter().map(|l| l.to_string()), 1).unwrap_err();
+        assert_eq!(err.location(), Some(Location::new(3, 9)));
+
+        // a single '@' starts a comment, however much it looks like an annotation
+        let comments = [
+            ">ann",
+            "@ the same can be done with -o",
+            "GGGAAACCC",
+            "@ P1 1-3",
+        ];
+        let records =
+            ParsedInput::parse_from(&mut comments.iter().map(|l| l.to_string()), 1).unwrap();
+        assert!(records[0].annotations.is_empty());
+
+        let typo = [">ann", "GGGAAACCC", "@@ P1 hlix3 color=2"];
+        let err = ParsedInput::parse_from(&mut typo.iter().map(|l| l.to_string()), 1).unwrap_err();
+        assert_eq!(err.location(), Some(Location::new(3, 7)));
+    }
+
     #[test]
     fn parse_multi_line() {
         let correct_pi = ParsedInput {
@@ -175,6 +924,9 @@ mod tests {
             secondary_structure: TENASST.to_string().into(),
             rna_name: TENANAME.to_string().into(),
             highlight: None,
+            pair_list: None,
+            energy: None,
+            annotations: vec![],
         };
 
         let pi = parse_helper(TENA);
@@ -183,4 +935,25 @@ mod tests {
         let pi = parse_helper(TENA_SHUFFLED);
         assert_eq!(correct_pi, pi);
     }
+
+    #[test]
+    fn token_columns() {
+        let line = " 1\tG  1 ";
+        let words = tokens(line);
+        assert_eq!(words, [(1, "1"), (3, "G"), (6, "1")]);
+        // the same token twice still gets its own column
+        assert_eq!(column_at(line, words[2].0), 7);
+        assert_eq!(column_at("żółw 1", tokens("żółw 1")[1].0), 6);
+        assert_eq!(indent(line), 1);
+    }
+
+    #[test]
+    fn safe_file_names() {
+        assert_eq!(safe_file_name("tRNA-Phe_1.2"), "tRNA-Phe_1.2");
+        assert_eq!(safe_file_name("../../etc/passwd"), "___.._etc_passwd");
+        assert_eq!(safe_file_name(".."), "__");
+        assert_eq!(safe_file_name("a/b\\c:d*"), "a_b_c_d_");
+        assert_eq!(safe_file_name("żółw"), "___w");
+        assert_eq!(safe_file_name(""), "rnaimg");
+    }
 }
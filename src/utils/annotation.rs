@@ -0,0 +1,367 @@
+//! Highlights given by ranges instead of a digit string aligned with the sequence.
+//! One annotation per line: a name, comma separated targets and optional settings
+//! ```text
+//! P1 12-25,80-93 color=#ff0000 label="P1"
+//! kink 40:61 color=3
+//! loop hairpin2
+//! ```
+//! targets are 1-indexed ranges (`12-25`), single nts (`30`), base pairs (`40:61`)
+//! or structure elements counted from the 5' end (`helix3`, `hairpin2`).
+//! color is a hex color or a digit picking one of the theme's highlights;
+//! without one annotations take the theme's highlights in turn.
+//! Annotations come from a file (--annotations) or from the input itself
+//! on lines starting with [MARKER].
+use plotters::style::RGBColor;
+
+use super::{collect_lines, column_at, read_lines, Result};
+use crate::draw::colors::{parse_hex, ColorTheme};
+use crate::draw::Region;
+use crate::error::{Location, ParseError};
+
+/// starts an annotation inside the input; a single '@' starts a comment
+pub const MARKER: &str = "@@";
+
+/// 0-indexed and inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Range(usize, usize),
+    Pair(usize, usize),
+    /// n-th helix (stack of consecutive pairs) counted from 1
+    Helix(usize),
+    /// unpaired nts of the n-th hairpin loop counted from 1
+    Hairpin(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HighlightColor {
+    Rgb(RGBColor),
+    /// index into ColorTheme.highlights
+    Theme(usize),
+}
+
+impl HighlightColor {
+    pub fn resolve(&self, theme: &ColorTheme) -> RGBColor {
+        match self {
+            Self::Rgb(color) => *color,
+            Self::Theme(idx) => theme.highlights[idx % theme.highlights.len()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub name: String,
+    pub targets: Vec<Target>,
+    pub color: Option<HighlightColor>,
+    pub label: Option<String>,
+    /// where it was defined; for errors found once the structure is known
+    pub at: Location,
+}
+
+/// splits on whitespace but keeps "quoted text" together;
+/// every token comes with the byte offset it starts at
+fn tokens(text: &str) -> Vec<(usize, &str)> {
+    let mut tokens = vec![];
+    let mut start = None;
+    let mut quoted = false;
+    for (i, c) in text.char_indices() {
+        match (start, c) {
+            (_, '"') => {
+                quoted = !quoted;
+                start.get_or_insert(i);
+            }
+            (Some(s), c) if c.is_whitespace() && !quoted => {
+                tokens.push((s, &text[s..i]));
+                start = None;
+            }
+            (None, c) if !c.is_whitespace() => start = Some(i),
+            _ => (),
+        }
+    }
+    if let Some(s) = start {
+        tokens.push((s, &text[s..]));
+    }
+    tokens
+}
+
+fn malformed(at: Location, reason: impl Into<String>) -> ParseError {
+    ParseError::malformed("annotation", at, reason)
+}
+
+fn position(token: &str, at: Location) -> Result<usize> {
+    match token.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n - 1),
+        _ => Err(malformed(at, format!("\"{token}\" is not a position"))),
+    }
+}
+
+fn target(token: &str, at: Location) -> Result<Target> {
+    let element = |prefix: &str| {
+        token
+            .strip_prefix(prefix)
+            .and_then(|n| n.parse::<usize>().ok())
+            .filter(|&n| n > 0)
+    };
+    if let Some(n) = element("helix") {
+        return Ok(Target::Helix(n));
+    }
+    if let Some(n) = element("hairpin") {
+        return Ok(Target::Hairpin(n));
+    }
+
+    if let Some((pos, pair)) = token.split_once(':') {
+        let (pos, pair) = (position(pos, at)?, position(pair, at)?);
+        return Ok(Target::Pair(pos.min(pair), pos.max(pair)));
+    }
+    let (start, end) = token.split_once('-').unwrap_or((token, token));
+    let (start, end) = (position(start, at)?, position(end, at)?);
+    if start > end {
+        return Err(malformed(at, format!("range {token} goes backwards")));
+    }
+    Ok(Target::Range(start, end))
+}
+
+impl Annotation {
+    /// parses the annotation found in the line from byte offset start on
+    pub fn parse(line: &str, start: usize, line_no: usize) -> Result<Self> {
+        let at = |offset: usize| Location::new(line_no, column_at(line, start + offset));
+        let tokens = tokens(&line[start..]);
+        let ((name_at, name), (targets_at, targets)) = match tokens[..] {
+            [name, targets, ..] => (name, targets),
+            [(name_at, _)] => return Err(malformed(at(name_at), "annotation without targets")),
+            [] => return Err(malformed(Location::new(line_no, 1), "empty annotation")),
+        };
+
+        let mut annotation = Annotation {
+            name: name.to_owned(),
+            targets: targets
+                .split(',')
+                .filter(|t| !t.is_empty())
+                .map(|t| target(t, at(targets_at)))
+                .collect::<Result<_>>()?,
+            color: None,
+            label: None,
+            at: at(name_at),
+        };
+
+        for &(setting_at, setting) in &tokens[2..] {
+            match setting.split_once('=') {
+                Some(("color", color)) => {
+                    annotation.color = match color.parse::<usize>() {
+                        Ok(digit @ 1..=9) => Some(HighlightColor::Theme(digit - 1)),
+                        _ => Some(HighlightColor::Rgb(parse_hex(color).ok_or_else(|| {
+                            malformed(at(setting_at), format!("\"{color}\" is not a color"))
+                        })?)),
+                    }
+                }
+                Some(("label", label)) => {
+                    annotation.label = Some(label.trim_matches('"').to_owned());
+                }
+                _ => {
+                    return Err(malformed(
+                        at(setting_at),
+                        format!("unknown setting \"{setting}\"; try color= or label="),
+                    ))
+                }
+            }
+        }
+        Ok(annotation)
+    }
+
+    /// n-th annotation without a color takes the n-th theme highlight
+    pub fn color_or_nth(&self, n: usize, theme: &ColorTheme) -> RGBColor {
+        self.color
+            .unwrap_or(HighlightColor::Theme(n))
+            .resolve(theme)
+    }
+
+    /// positions covered by the annotation in the given structure
+    pub fn positions(&self, pair_list: &[Option<usize>]) -> Result<Vec<usize>> {
+        let len = pair_list.len();
+        let mut positions = vec![];
+        for target in &self.targets {
+            match *target {
+                Target::Range(start, end) if end < len => positions.extend(start..=end),
+                Target::Pair(pos, pair) if pair < len => positions.extend([pos, pair]),
+                Target::Helix(n) => match helices(pair_list).get(n - 1) {
+                    Some(&(pos, pair, stacked)) => {
+                        positions.extend(pos..pos + stacked);
+                        positions.extend(pair + 1 - stacked..=pair);
+                    }
+                    None => return Err(self.missing(&format!("helix{n}"))),
+                },
+                Target::Hairpin(n) => match hairpins(pair_list).get(n - 1) {
+                    Some(&(pos, pair)) => positions.extend(pos + 1..pair),
+                    None => return Err(self.missing(&format!("hairpin{n}"))),
+                },
+                _ => {
+                    return Err(malformed(
+                        self.at,
+                        format!(
+                            "{} reaches past the end of the sequence ({len} nt)",
+                            self.name
+                        ),
+                    ))
+                }
+            }
+        }
+        Ok(positions)
+    }
+
+    fn missing(&self, element: &str) -> ParseError {
+        malformed(self.at, format!("{}: there is no {element}", self.name))
+    }
+
+    pub fn parse_lines<S: AsRef<str>>(lines: &[S]) -> Result<Vec<Self>> {
+        lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.as_ref()))
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .map(|(line_no, line)| Self::parse(line, 0, line_no))
+            .collect()
+    }
+
+    pub fn from_file(path: &str) -> Result<Vec<Self>> {
+        let lines = read_lines(path).map_err(|source| ParseError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        Self::parse_lines(&collect_lines(lines, path)?)
+    }
+}
+
+/// (5' pos, 3' pos, number of stacked pairs) of the outermost pair of every helix
+fn helices(pair_list: &[Option<usize>]) -> Vec<(usize, usize, usize)> {
+    let stacked_on =
+        |pos: usize, pair: usize| pos > 0 && pair_list.get(pair + 1) == Some(&Some(pos - 1));
+    pair_list
+        .iter()
+        .enumerate()
+        .filter_map(|(pos, pair)| pair.filter(|&pair| pair > pos).map(|pair| (pos, pair)))
+        .filter(|&(pos, pair)| !stacked_on(pos, pair))
+        .map(|(pos, pair)| {
+            let stacked = (0..)
+                .take_while(|&k| pos + k < pair - k && pair_list[pos + k] == Some(pair - k))
+                .count();
+            (pos, pair, stacked)
+        })
+        .collect()
+}
+
+/// closing pairs of loops with nothing paired inside
+fn hairpins(pair_list: &[Option<usize>]) -> Vec<(usize, usize)> {
+    pair_list
+        .iter()
+        .enumerate()
+        .filter_map(|(pos, pair)| pair.filter(|&pair| pair > pos).map(|pair| (pos, pair)))
+        .filter(|&(pos, pair)| pair_list[pos + 1..pair].iter().all(Option::is_none))
+        .collect()
+}
+
+/// per position highlight colors; later annotations paint over earlier ones
+pub fn highlight_colors(
+    annotations: &[Annotation],
+    pair_list: &[Option<usize>],
+    theme: &ColorTheme,
+) -> Result<Vec<Option<RGBColor>>> {
+    let mut colors = vec![None; pair_list.len()];
+    for (n, annotation) in annotations.iter().enumerate() {
+        let color = annotation.color_or_nth(n, theme);
+        for pos in annotation.positions(pair_list)? {
+            colors[pos] = Some(color);
+        }
+    }
+    Ok(colors)
+}
+
+/// labeled annotations as regions to name on the drawing
+pub fn regions(
+    annotations: &[Annotation],
+    pair_list: &[Option<usize>],
+    theme: &ColorTheme,
+) -> Result<Vec<Region>> {
+    let mut regions = vec![];
+    for (n, annotation) in annotations.iter().enumerate() {
+        if let Some(text) = &annotation.label {
+            regions.push(Region {
+                positions: annotation.positions(pair_list)?,
+                text: text.clone(),
+                color: annotation.color_or_nth(n, theme),
+            });
+        }
+    }
+    Ok(regions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rnamanip::get_pair_list;
+
+    #[test]
+    fn parse_annotations() {
+        let lines = [
+            "# comment",
+            r#"P1 12-15,20 color=#ff0000 label="P1 helix""#,
+            "",
+            "kink 3:9,hairpin1 color=2",
+        ];
+        let annotations = Annotation::parse_lines(&lines).unwrap();
+        assert_eq!(annotations.len(), 2);
+        assert_eq!(
+            annotations[0].targets,
+            vec![Target::Range(11, 14), Target::Range(19, 19)]
+        );
+        assert_eq!(
+            annotations[0].color,
+            Some(HighlightColor::Rgb(RGBColor(255, 0, 0)))
+        );
+        assert_eq!(annotations[0].label.as_deref(), Some("P1 helix"));
+        assert_eq!(
+            annotations[1].targets,
+            vec![Target::Pair(2, 8), Target::Hairpin(1)]
+        );
+        assert_eq!(annotations[1].color, Some(HighlightColor::Theme(1)));
+        assert_eq!(annotations[1].at, Location::new(4, 1));
+
+        let err = Annotation::parse_lines(&["P2 5-3"]).unwrap_err();
+        assert_eq!(err.location(), Some(Location::new(1, 4)));
+        assert!(Annotation::parse_lines(&["P2 5 colour=red"]).is_err());
+        assert!(Annotation::parse_lines(&["P2"]).is_err());
+    }
+
+    #[test]
+    fn elements_and_positions() {
+        let pair_list = get_pair_list("((.((...)).))..(())").unwrap();
+        assert_eq!(
+            helices(&pair_list),
+            vec![(0, 12, 2), (3, 9, 2), (15, 18, 2)]
+        );
+        assert_eq!(hairpins(&pair_list), vec![(4, 8), (16, 17)]);
+
+        let parse = |text: &str| Annotation::parse(text, 0, 1).unwrap();
+        assert_eq!(
+            parse("a helix2").positions(&pair_list).unwrap(),
+            vec![3, 4, 8, 9]
+        );
+        assert_eq!(
+            parse("a hairpin1").positions(&pair_list).unwrap(),
+            vec![5, 6, 7]
+        );
+        assert!(parse("a hairpin3").positions(&pair_list).is_err());
+        assert!(parse("a 18-20").positions(&pair_list).is_err());
+
+        let theme = ColorTheme::dark();
+        let colors = highlight_colors(
+            &[parse("a 1-3"), parse("b 3:19 color=#000000")],
+            &pair_list,
+            &theme,
+        )
+        .unwrap();
+        assert_eq!(colors[0], Some(theme.highlights[0]));
+        assert_eq!(colors[2], Some(RGBColor(0, 0, 0)));
+        assert_eq!(colors[18], Some(RGBColor(0, 0, 0)));
+        assert_eq!(colors[3], None);
+    }
+}
@@ -0,0 +1,260 @@
+//! Reads BPSEQ files as exported by CRW and RNA STRAND:
+//! ```text
+//! Filename: tiny.bpseq
+//! 1 G 8
+//! 2 G 7
+//! 3 A 0
+//! ```
+//! one line per nucleotide: index nucleotide pair, where pair 0 means unpaired.
+//! Lines before the first nucleotide are treated as a header;
+//! "Filename:" if present becomes the name.
+use super::{column_at, indent, tokens, ParsedInput};
+use crate::error::{Location, ParseError};
+use crate::rnamanip::Nucleotide;
+use std::fmt::Write;
+
+/// "1 G 8" split into its columns if the line looks like one
+struct Row<'a> {
+    index: usize,
+    nt: &'a str,
+    pair: usize,
+    /// byte offsets of the nucleotide and pair columns
+    nt_at: usize,
+    pair_at: usize,
+}
+
+fn columns(line: &str) -> Option<Row<'_>> {
+    let [(_, index), (nt_at, nt), (pair_at, pair)] = tokens(line)[..] else {
+        return None;
+    };
+    Some(Row {
+        index: index.parse().ok()?,
+        nt,
+        pair: pair.parse().ok()?,
+        nt_at,
+        pair_at,
+    })
+}
+
+/// true if the first nucleotide line is preceded only by a header
+pub fn sniff(lines: &[String]) -> bool {
+    lines
+        .iter()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .find(|l| columns(l).is_some() || !is_header(l))
+        .and_then(columns)
+        .is_some_and(|row| row.index == 1)
+}
+
+fn is_header(line: &str) -> bool {
+    line.contains(':') || line.starts_with('#')
+}
+
+fn extract_name(line: &str) -> Option<String> {
+    let (key, value) = line.split_once(':')?;
+    if !key.trim().eq_ignore_ascii_case("filename") {
+        return None;
+    }
+    let value = value.trim();
+    let stem = value.strip_suffix(".bpseq").unwrap_or(value);
+    if stem.is_empty() {
+        None
+    } else {
+        Some(stem.replace(' ', "_"))
+    }
+}
+
+/// parses single BPSEQ structure
+pub fn parse(lines: &[String]) -> Result<ParsedInput, ParseError> {
+    let mut rna_name = None;
+    let mut sequence = String::new();
+    let mut pairs = vec![];
+
+    for (line_no, line) in lines.iter().enumerate().map(|(i, l)| (i + 1, l.as_str())) {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        let Some(row) = columns(line) else {
+            if pairs.is_empty() && is_header(trimmed) {
+                rna_name = rna_name.or_else(|| extract_name(trimmed));
+                continue;
+            }
+            return Err(ParseError::malformed(
+                "bpseq",
+                Location::new(line_no, column_at(line, indent(line))),
+                format!("expected \"index nucleotide pair\", found \"{trimmed}\""),
+            ));
+        };
+
+        if row.index != pairs.len() + 1 {
+            return Err(ParseError::malformed(
+                "bpseq",
+                Location::new(line_no, column_at(line, indent(line))),
+                format!("expected index {}, found {}", pairs.len() + 1, row.index),
+            ));
+        }
+
+        let at = Location::new(line_no, column_at(line, row.nt_at));
+        let mut symbols = row.nt.chars();
+        let (Some(symbol), None) = (symbols.next(), symbols.next()) else {
+            return Err(ParseError::malformed(
+                "bpseq",
+                at,
+                "expected a single nucleotide",
+            ));
+        };
+        if Nucleotide::from_char(symbol).is_none() {
+            return Err(ParseError::InvalidNucleotide { symbol, at });
+        }
+        sequence.push(symbol);
+        pairs.push((
+            Location::new(line_no, column_at(line, row.pair_at)),
+            row.pair,
+        ));
+    }
+
+    let n = pairs.len();
+    if n == 0 {
+        return Err(ParseError::NothingToDraw);
+    }
+
+    let mut pair_list = vec![None; n];
+    for (pos, &(at, pair)) in pairs.iter().enumerate() {
+        if pair == 0 {
+            continue;
+        }
+        if pair > n {
+            return Err(ParseError::malformed(
+                "bpseq",
+                at,
+                format!("pair {pair} is out of range 1..={n}"),
+            ));
+        }
+        if pair == pos + 1 {
+            return Err(ParseError::malformed(
+                "bpseq",
+                at,
+                format!("{pair} pairs with itself"),
+            ));
+        }
+        let partner = pairs.get(pair - 1).map(|(_, p)| *p);
+        if partner != Some(pos + 1) {
+            return Err(ParseError::malformed(
+                "bpseq",
+                at,
+                format!("{} pairs with {pair} but not the other way around", pos + 1),
+            ));
+        }
+        pair_list[pos] = Some(pair - 1);
+    }
+
+    Ok(ParsedInput {
+        sequence: Some(sequence),
+        rna_name,
+        pair_list: Some(pair_list),
+        ..ParsedInput::default()
+    })
+}
+
+/// writes structure as BPSEQ; name ends up in the "Filename:" header
+pub fn write(name: Option<&str>, sequence: &[Nucleotide], pair_list: &[Option<usize>]) -> String {
+    let mut out = String::with_capacity(12 * (pair_list.len() + 1));
+    if let Some(name) = name {
+        writeln!(out, "Filename: {name}.bpseq").unwrap();
+    }
+    for (pos, pair) in pair_list.iter().enumerate() {
+        writeln!(
+            out,
+            "{} {} {}",
+            pos + 1,
+            sequence[pos],
+            pair.map_or(0, |p| p + 1)
+        )
+        .unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rnamanip::read_sequence;
+    use crate::utils::testing::{error_with, lines};
+
+    const TINY: &str = "\
+Filename: tiny hairpin.bpseq
+Organism: Nowhere
+Accession Number: none
+1 G 8
+2 G 7
+3 A 0
+4 A 0
+5 A 0
+6 A 0
+7 C 2
+8 C 1
+";
+
+    #[test]
+    fn parse_bpseq() {
+        let lines = lines(TINY);
+        assert!(sniff(&lines));
+        let pi = parse(&lines).expect("valid bpseq");
+        assert_eq!(pi.sequence.as_deref(), Some("GGAAAACC"));
+        assert_eq!(pi.rna_name.as_deref(), Some("tiny_hairpin"));
+        let pl = pi.pair_list.unwrap();
+        assert_eq!(pl[0], Some(7));
+        assert_eq!(pl[7], Some(0));
+        assert_eq!(pl[4], None);
+    }
+
+    #[test]
+    fn write_bpseq() {
+        let seq = read_sequence("GAUC").unwrap();
+        let pair_list = [Some(3), None, None, Some(0)];
+        assert_eq!(
+            write(Some("tiny"), &seq, &pair_list),
+            "Filename: tiny.bpseq\n1 G 4\n2 A 0\n3 U 0\n4 C 1\n"
+        );
+        assert_eq!(
+            write(None, &seq, &pair_list),
+            "1 G 4\n2 A 0\n3 U 0\n4 C 1\n"
+        );
+    }
+
+    #[test]
+    fn pair_columns() {
+        // 0 means unpaired, a missing pair is an error
+        assert_eq!(
+            error_with(TINY, "3 A 0", "3 A", parse),
+            "bpseq: expected \"index nucleotide pair\", found \"3 A\" at line 6 col 1"
+        );
+        assert_eq!(
+            error_with(TINY, "5 A 0", "5 A 9", parse),
+            "bpseq: pair 9 is out of range 1..=8 at line 8 col 5"
+        );
+        // the same number in the index and pair columns
+        assert_eq!(
+            error_with(TINY, "2 G 7", "2 G 2", parse),
+            "bpseq: 2 pairs with itself at line 5 col 5"
+        );
+    }
+
+    #[test]
+    fn reject_broken_bpseq() {
+        assert_eq!(
+            error_with(TINY, "8 C 1", "8 C 3", parse),
+            "bpseq: 1 pairs with 8 but not the other way around at line 4 col 5"
+        );
+        assert_eq!(
+            error_with(TINY, "3 A 0", "3 GA 0", parse),
+            "bpseq: expected a single nucleotide at line 6 col 3"
+        );
+        assert!(parse(&lines(&TINY.replace("3 A 0", "4 A 0"))).is_err());
+        assert!(parse(&lines("Filename: empty")).is_err());
+        assert!(!sniff(&lines("GGAAAACC\n((....))")));
+    }
+}
@@ -0,0 +1,328 @@
+//! Reads CT (connectivity table) files as produced by RNAstructure,
+//! mfold and most structure databases. Every structure starts with a header:
+//! ```text
+//! 73  ENERGY = -17.5  tRNA
+//! ```
+//! followed by one line per nucleotide:
+//! ```text
+//! index nucleotide index-1 index+1 pair natural_index
+//! ```
+//! where pair 0 means unpaired. One file may hold several structures back to back.
+use super::{column_at, indent, tokens, ParsedInput};
+use crate::error::{Location, ParseError};
+use crate::rnamanip::Nucleotide;
+use std::fmt::Write;
+
+/// header line is an integer followed by anything (or nothing at all)
+fn parse_header(line: &str) -> Option<(usize, &str)> {
+    let trimmed = line.trim();
+    let split = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+    let n = trimmed[..split].parse().ok()?;
+    Some((n, trimmed[split..].trim()))
+}
+
+/// strips "ENERGY = -12.3" or "dG = -12.3" so only the title remains
+fn extract_name(title: &str) -> Option<String> {
+    let mut tokens = title.split_whitespace().peekable();
+    let mut name = vec![];
+    while let Some(token) = tokens.next() {
+        let lowered = token.to_ascii_lowercase();
+        if lowered == "energy" || lowered == "dg" {
+            if tokens.peek() == Some(&"=") {
+                tokens.next();
+            }
+            tokens.next();
+        } else if lowered.starts_with("energy=") || lowered.starts_with("dg=") {
+            if lowered.ends_with('=') {
+                tokens.next();
+            }
+        } else {
+            name.push(token);
+        }
+    }
+
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.join("_"))
+    }
+}
+
+/// energy from "ENERGY = -12.3", "dG = -12.3" or "ENERGY=-12.3"
+fn extract_energy(title: &str) -> Option<f64> {
+    let lowered = title.to_ascii_lowercase().replace('=', " = ");
+    let mut tokens = lowered.split_whitespace();
+    tokens.find(|&t| t == "energy" || t == "dg")?;
+    match tokens.next()? {
+        "=" => tokens.next()?.parse().ok(),
+        _ => None,
+    }
+}
+
+/// true if the lines look like a CT file: a header followed
+/// by a 6 column line describing the first nucleotide
+pub fn sniff(lines: &[String]) -> bool {
+    let mut lines = lines.iter().filter(|l| !l.trim().is_empty());
+    let header = lines.next().and_then(|l| parse_header(l));
+    let first = lines
+        .next()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>());
+    match (header, first) {
+        (Some(_), Some(columns)) => columns.len() == 6 && columns[0] == "1",
+        _ => false,
+    }
+}
+
+/// parses all structures found in the CT lines
+pub fn parse(lines: &[String]) -> Result<Vec<ParsedInput>, ParseError> {
+    let mut records = vec![];
+    let mut lines = lines
+        .iter()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l.as_str()))
+        .filter(|(_, l)| !l.trim().is_empty());
+
+    while let Some((header_no, header)) = lines.next() {
+        let (n, title) = parse_header(header).ok_or_else(|| {
+            ParseError::malformed(
+                "ct",
+                Location::new(header_no, column_at(header, indent(header))),
+                "expected header starting with the length",
+            )
+        })?;
+
+        let mut sequence = String::with_capacity(n);
+        let mut pair_list = vec![None; n];
+        let mut line_numbers = Vec::with_capacity(n);
+
+        for expected in 1..=n {
+            let (line_no, line) = lines.next().ok_or_else(|| {
+                ParseError::malformed(
+                    "ct",
+                    Location::new(header_no, 1),
+                    format!("structure ended after {} nts, expected {n}", expected - 1),
+                )
+            })?;
+            let (offsets, columns): (Vec<usize>, Vec<&str>) = tokens(line).into_iter().unzip();
+            let at = |column: usize| Location::new(line_no, column_at(line, offsets[column]));
+
+            if columns.len() < 6 {
+                return Err(ParseError::malformed(
+                    "ct",
+                    Location::new(line_no, 1),
+                    format!("expected 6 columns, found {}", columns.len()),
+                ));
+            }
+
+            let index: usize = columns[0].parse().map_err(|_| {
+                ParseError::malformed("ct", at(0), format!("invalid index {}", columns[0]))
+            })?;
+            if index != expected {
+                return Err(ParseError::malformed(
+                    "ct",
+                    at(0),
+                    format!("expected index {expected}, found {index}"),
+                ));
+            }
+
+            let pair: usize = columns[4].parse().map_err(|_| {
+                ParseError::malformed("ct", at(4), format!("invalid pair {}", columns[4]))
+            })?;
+            if pair > n {
+                return Err(ParseError::malformed(
+                    "ct",
+                    at(4),
+                    format!("pair {pair} is out of range 1..={n}"),
+                ));
+            }
+            if pair == index {
+                return Err(ParseError::malformed(
+                    "ct",
+                    at(4),
+                    format!("{index} pairs with itself"),
+                ));
+            }
+            if pair != 0 {
+                pair_list[index - 1] = Some(pair - 1);
+            }
+
+            let mut symbols = columns[1].chars();
+            let (Some(symbol), None) = (symbols.next(), symbols.next()) else {
+                return Err(ParseError::malformed(
+                    "ct",
+                    at(1),
+                    "expected a single nucleotide",
+                ));
+            };
+            if Nucleotide::from_char(symbol).is_none() {
+                return Err(ParseError::InvalidNucleotide { symbol, at: at(1) });
+            }
+            sequence.push(symbol);
+            line_numbers.push(line_no);
+        }
+
+        for (pos, pair) in pair_list.iter().enumerate() {
+            if let Some(pair) = pair {
+                if pair_list[*pair] != Some(pos) {
+                    return Err(ParseError::malformed(
+                        "ct",
+                        Location::new(line_numbers[pos], 1),
+                        format!(
+                            "{} pairs with {} but not the other way around",
+                            pos + 1,
+                            pair + 1
+                        ),
+                    ));
+                }
+            }
+        }
+
+        records.push(ParsedInput {
+            sequence: Some(sequence),
+            pair_list: Some(pair_list),
+            rna_name: extract_name(title),
+            energy: extract_energy(title),
+            ..ParsedInput::default()
+        });
+    }
+
+    Ok(records)
+}
+
+/// writes single structure as a CT table
+pub fn write(name: Option<&str>, sequence: &[Nucleotide], pair_list: &[Option<usize>]) -> String {
+    let n = pair_list.len();
+    let mut out = String::with_capacity(40 * (n + 1));
+    writeln!(out, "{n:>5}  {}", name.unwrap_or("rnapkin")).unwrap();
+    for (pos, pair) in pair_list.iter().enumerate() {
+        let index = pos + 1;
+        let next = if index == n { 0 } else { index + 1 };
+        let pair = pair.map_or(0, |p| p + 1);
+        writeln!(
+            out,
+            "{index:>5} {} {:>7} {next:>4} {pair:>4} {index:>4}",
+            sequence[pos], pos
+        )
+        .unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rnamanip::read_sequence;
+    use crate::utils::testing::{error_with, lines};
+
+    const DOUBLE_CT: &str = "\
+  8  ENERGY = -1.2  tiny hairpin
+    1 G       0    2    8    1
+    2 G       1    3    7    2
+    3 A       2    4    0    3
+    4 A       3    5    0    4
+    5 A       4    6    0    5
+    6 A       5    7    0    6
+    7 C       6    8    2    7
+    8 C       7    9    1    8
+  4 dG = 0.0  flat
+    1 A       0    2    0    1
+    2 C       1    3    0    2
+    3 G       2    4    0    3
+    4 U       3    5    0    4
+";
+
+    #[test]
+    fn parse_multiple_structures() {
+        let lines = lines(DOUBLE_CT);
+        assert!(sniff(&lines));
+        let records = parse(&lines).expect("valid ct");
+        assert_eq!(records.len(), 2);
+
+        let hairpin = &records[0];
+        assert_eq!(hairpin.sequence.as_deref(), Some("GGAAAACC"));
+        assert_eq!(hairpin.rna_name.as_deref(), Some("tiny_hairpin"));
+        assert_eq!(hairpin.energy, Some(-1.2));
+        let pl = hairpin.pair_list.as_ref().unwrap();
+        assert_eq!(pl[0], Some(7));
+        assert_eq!(pl[6], Some(1));
+        assert_eq!(pl[3], None);
+
+        assert_eq!(records[1].rna_name.as_deref(), Some("flat"));
+        assert_eq!(records[1].energy, Some(0.));
+        assert!(records[1]
+            .pair_list
+            .as_ref()
+            .unwrap()
+            .iter()
+            .all(|p| p.is_none()));
+    }
+
+    #[test]
+    fn write_ct() {
+        let seq = read_sequence("GAUC").unwrap();
+        let written = write(Some("tiny"), &seq, &[Some(3), None, None, Some(0)]);
+        assert_eq!(
+            written,
+            "    4  tiny\n\
+            \x20   1 G       0    2    4    1\n\
+            \x20   2 A       1    3    0    2\n\
+            \x20   3 U       2    4    0    3\n\
+            \x20   4 C       3    0    1    4\n"
+        );
+    }
+
+    #[test]
+    fn header_counts_nucleotides() {
+        // rows past the count are read as the header of the next structure
+        let short = DOUBLE_CT.replacen("8  ENERGY", "6  ENERGY", 1);
+        assert_eq!(
+            parse(&lines(&short)).unwrap_err().to_string(),
+            "ct: pair 8 is out of range 1..=6 at line 2 col 25"
+        );
+        let long = DOUBLE_CT.replacen("8  ENERGY", "9  ENERGY", 1);
+        assert_eq!(
+            parse(&lines(&long)).unwrap_err().to_string(),
+            "ct: expected 6 columns, found 5 at line 10 col 1"
+        );
+        let truncated: Vec<String> = lines(DOUBLE_CT).into_iter().take(5).collect();
+        assert_eq!(
+            parse(&truncated).unwrap_err().to_string(),
+            "ct: structure ended after 4 nts, expected 8 at line 1 col 1"
+        );
+    }
+
+    #[test]
+    fn missing_pair_column() {
+        // 0 means unpaired; a missing column is an error rather than unpaired
+        assert_eq!(
+            error_with(
+                DOUBLE_CT,
+                "    3 A       2    4    0    3",
+                "    3 A       2    4    3",
+                parse
+            ),
+            "ct: expected 6 columns, found 5 at line 4 col 1"
+        );
+    }
+
+    #[test]
+    fn reject_broken_ct() {
+        assert_eq!(
+            error_with(DOUBLE_CT, "8    1\n", "6    1\n", parse),
+            "ct: 1 pairs with 6 but not the other way around at line 2 col 1"
+        );
+        assert_eq!(
+            error_with(DOUBLE_CT, "3 A", "3 Z", parse),
+            "invalid nucleotide 'Z' at line 4 col 7"
+        );
+        assert_eq!(
+            error_with(DOUBLE_CT, "3 A", "3 AG", parse),
+            "ct: expected a single nucleotide at line 4 col 7"
+        );
+        assert_eq!(
+            error_with(DOUBLE_CT, "4    0    3", "4    3    3", parse),
+            "ct: 3 pairs with itself at line 4 col 25"
+        );
+        assert!(!sniff(&lines(">name\nACGU\n....")));
+    }
+}
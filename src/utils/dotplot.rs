@@ -0,0 +1,145 @@
+//! Reads base pair probabilities from ViennaRNA dot plots (`dot.ps`, `_dp.ps`)
+//! or from plain lists of 1-indexed `i j p` lines. In dot plots the
+//! probabilities are the lines ending with `ubox`, holding the square root of p:
+//! ```text
+//! 1 24 0.9911 ubox
+//! ```
+//! `lbox` lines are the MFE structure and are left out.
+use super::{collect_lines, column_at, read_lines, tokens, Result};
+use crate::error::{Location, ParseError};
+
+/// (5' pos, 3' pos, probability); 0-indexed
+pub type PairProbability = (usize, usize, f64);
+
+pub fn parse_pair_probabilities<S: AsRef<str>>(lines: &[S]) -> Result<Vec<PairProbability>> {
+    let postscript = lines
+        .first()
+        .is_some_and(|line| line.as_ref().starts_with("%!PS"));
+    let mut probabilities = vec![];
+
+    for (i, line) in lines.iter().enumerate() {
+        let line = line.as_ref();
+        let (pos, pair, p, squared) = match tokens(line)[..] {
+            [pos, pair, p, (_, "ubox")] => (pos, pair, p, true),
+            [pos, pair, p] if !postscript && !pos.1.starts_with('#') => (pos, pair, p, false),
+            _ => continue,
+        };
+        let at = |offset: usize| Location::new(i + 1, column_at(line, offset));
+
+        let index = |(offset, token): (usize, &str)| match token.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n - 1),
+            _ => Err(ParseError::malformed(
+                "pair probabilities",
+                at(offset),
+                "position should be a positive integer",
+            )),
+        };
+        let (pos, pair) = (index(pos)?, index(pair)?);
+        let (p_at, p) = p;
+        let p = match p.parse::<f64>() {
+            Ok(v) if squared => v * v,
+            Ok(v) => v,
+            Err(_) => {
+                return Err(ParseError::malformed(
+                    "pair probabilities",
+                    at(p_at),
+                    format!("\"{p}\" is not a number"),
+                ))
+            }
+        };
+        if !(0. ..=1.).contains(&p) {
+            return Err(ParseError::malformed(
+                "pair probabilities",
+                at(p_at),
+                "probability should be between 0 and 1",
+            ));
+        }
+        probabilities.push((pos.min(pair), pos.max(pair), p));
+    }
+    Ok(probabilities)
+}
+
+pub fn read_pair_probabilities(path: &str) -> Result<Vec<PairProbability>> {
+    let lines = read_lines(path).map_err(|source| ParseError::Io {
+        path: path.to_owned(),
+        source,
+    })?;
+    parse_pair_probabilities(&collect_lines(lines, path)?)
+}
+
+/// how sure the ensemble is about the given structure at every position:
+/// probability of the pair a nucleotide is in, or of being unpaired if it's unpaired
+pub fn confidence(
+    probabilities: &[PairProbability],
+    pair_list: &[Option<usize>],
+) -> Vec<Option<f64>> {
+    let len = pair_list.len();
+    let mut paired = vec![0.; len];
+    let mut of_pair = vec![0.; len];
+    for &(pos, pair, p) in probabilities.iter().filter(|(_, pair, _)| *pair < len) {
+        paired[pos] += p;
+        paired[pair] += p;
+        if pair_list[pos] == Some(pair) {
+            of_pair[pos] = p;
+            of_pair[pair] = p;
+        }
+    }
+
+    pair_list
+        .iter()
+        .enumerate()
+        .map(|(pos, pair)| match pair {
+            Some(_) => Some(of_pair[pos]),
+            None => Some((1. - paired[pos]).clamp(0., 1.)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOT_PS: &str = "%!PS-Adobe-3.0 EPSF-3.0
+%%Title: RNA Dot Plot
+/sequence { (\\
+GGGGAAAACCCC\\
+) } def
+72 216 translate
+%start of base pair probability data
+1 12 0.9 ubox
+2 11 0.8 ubox
+3 10 0.5 ubox
+1 12 0.95 lbox
+showpage";
+
+    #[test]
+    fn dot_plot_and_plain_list() {
+        let lines: Vec<&str> = DOT_PS.lines().collect();
+        let probabilities = parse_pair_probabilities(&lines).unwrap();
+        assert_eq!(probabilities.len(), 3);
+        assert_eq!(probabilities[0].0, 0);
+        assert_eq!(probabilities[0].1, 11);
+        assert!((probabilities[0].2 - 0.81).abs() < 1e-9);
+
+        let plain = ["# i j p", "12 1 0.5", "2 11 0.25"];
+        assert_eq!(
+            parse_pair_probabilities(&plain).unwrap(),
+            vec![(0, 11, 0.5), (1, 10, 0.25)]
+        );
+
+        let err = parse_pair_probabilities(&["1 12 1.5"]).unwrap_err();
+        assert_eq!(err.location(), Some(Location::new(1, 6)));
+    }
+
+    #[test]
+    fn confidence_of_structure() {
+        let pair_list = crate::rnamanip::get_pair_list("((....))").unwrap();
+        let probabilities = [(0, 7, 0.9), (1, 6, 0.6), (2, 5, 0.3)];
+        let confidence = confidence(&probabilities, &pair_list);
+        assert_eq!(confidence[0], Some(0.9));
+        assert_eq!(confidence[6], Some(0.6));
+        // unpaired with the probability of pairing taken away
+        assert_eq!(confidence[2], Some(0.7));
+        assert_eq!(confidence[3], Some(1.));
+    }
+}
@@ -0,0 +1,106 @@
+//! Reads per-nucleotide numeric data such as SHAPE or DMS reactivities.
+//! `.shape` files hold a 1-indexed position and a value per line:
+//! ```text
+//! 1   -999
+//! 2   0.154
+//! ```
+//! `.map` files (ShapeMapper) add standard error and the nucleotide:
+//! ```text
+//! 1   0.154   0.021   G
+//! ```
+//! Only the first two columns are used, so both are read the same way.
+//! A lone column is taken as values for consecutive positions.
+//! -999 (or anything at or below it), nan and missing positions are treated as no data.
+use super::{collect_lines, column_at, read_lines, tokens, Result};
+use crate::error::{Location, ParseError};
+
+/// conventional marker of missing data in .shape and .map files
+const MISSING: f64 = -999.;
+
+fn value(token: &str) -> Option<f64> {
+    match token.parse::<f64>() {
+        Ok(v) if v.is_nan() || v <= MISSING => None,
+        Ok(v) => Some(v),
+        Err(_) => None,
+    }
+}
+
+/// values indexed by 0-indexed position; None where there's no data
+pub fn parse_reactivity<S: AsRef<str>>(lines: &[S]) -> Result<Vec<Option<f64>>> {
+    let mut values = vec![];
+    for (i, line) in lines.iter().enumerate() {
+        let line = line.as_ref();
+        let words = tokens(line);
+        let (first, second) = match (words.first(), words.get(1)) {
+            (None, _) => continue,
+            (Some((_, t)), _) if t.starts_with('#') => continue,
+            (Some(&first), second) => (first, second.copied()),
+        };
+        let at = |offset: usize| Location::new(i + 1, column_at(line, offset));
+
+        let (pos, (offset, token)) = match second {
+            Some(second) => match first.1.parse::<usize>() {
+                Ok(pos) if pos > 0 => (pos - 1, second),
+                _ => {
+                    return Err(ParseError::malformed(
+                        "reactivity",
+                        at(first.0),
+                        "position should be a positive integer",
+                    ))
+                }
+            },
+            None => (values.len(), first),
+        };
+
+        if token.parse::<f64>().is_err() {
+            return Err(ParseError::malformed(
+                "reactivity",
+                at(offset),
+                format!("\"{token}\" is not a number"),
+            ));
+        }
+        if values.len() <= pos {
+            values.resize(pos + 1, None);
+        }
+        values[pos] = value(token);
+    }
+    Ok(values)
+}
+
+pub fn read_reactivity(path: &str) -> Result<Vec<Option<f64>>> {
+    let lines = read_lines(path).map_err(|source| ParseError::Io {
+        path: path.to_owned(),
+        source,
+    })?;
+    parse_reactivity(&collect_lines(lines, path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shape_and_map_files() {
+        let shape = ["1 -999", "2 0.154", "", "4 1.2"];
+        assert_eq!(
+            parse_reactivity(&shape).unwrap(),
+            vec![None, Some(0.154), None, Some(1.2)]
+        );
+
+        let map = ["1\t0.5\t0.02\tG", "2\t-999\t0\tA", "3\tnan\t0\tC"];
+        assert_eq!(parse_reactivity(&map).unwrap(), vec![Some(0.5), None, None]);
+
+        let column = ["# conservation", "0.9", "0.1"];
+        assert_eq!(
+            parse_reactivity(&column).unwrap(),
+            vec![Some(0.9), Some(0.1)]
+        );
+    }
+
+    #[test]
+    fn malformed_lines() {
+        let err = parse_reactivity(&["1 0.2", "2 high"]).unwrap_err();
+        assert_eq!(err.location(), Some(Location::new(2, 3)));
+        assert!(parse_reactivity(&["0 0.2"]).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::while_let_loop, clippy::iter_skip_next)]
+
 use rnapkin::forest::{grow_tree, DotBracket, Tree};
 use rnapkin::rnamanip::get_pair_list;
 use std::fs::File;
@@ -31,7 +33,7 @@ fn pair_list_creation() {
             .map(|x| x.parse::<usize>().ok())
             .collect();
 
-        let rnapking_pair_list = get_pair_list(&structure);
+        let rnapking_pair_list = get_pair_list(&structure).expect("valid structure");
         assert_eq!(rnapking_pair_list, pair_list)
     }
 }